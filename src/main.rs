@@ -1,4 +1,5 @@
-use std::error::Error;
+use std::io::IsTerminal;
+use std::process::ExitCode;
 
 use tracing::{Level, debug};
 use tracing_subscriber::fmt::format::FmtSpan;
@@ -22,16 +23,22 @@ fn initialize_tracing(cli: &Cli) {
     tracing_subscriber::fmt::fmt()
         .with_span_events(FmtSpan::NONE)
         .with_target(false)
+        // The widget shows warnings with `zle -M`
+        .without_time()
+        .with_ansi(std::io::stderr().is_terminal())
         .with_max_level(verbosity_to_log_level(cli.verbose))
         .with_writer(std::io::stderr)
         .init();
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> ExitCode {
     let cli: Cli = clap::Parser::parse();
 
     initialize_tracing(&cli);
     debug!(?cli);
-    zle::go(cli.cmd)?;
-    Ok(())
+    if let Err(e) = zle::go(cli.cmd) {
+        eprintln!("zbr: {e}");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
 }
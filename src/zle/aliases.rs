@@ -1,6 +1,5 @@
 use std::collections::BTreeMap;
 
-use super::compile::compile_with_prefixes;
 use super::expand;
 
 // TODO: handle subcommands properly
@@ -21,9 +20,7 @@ fn make_case(prefix: &str, compiled: &BTreeMap<&str, &str>) {
 }
 
 pub(super) fn go(conf: expand::ConfigFile) {
-    let mut compiled = compile_with_prefixes(&conf.cmds, "", true)
-        .into_iter()
-        .collect::<Vec<_>>();
+    let mut compiled = conf.compile_all().into_iter().collect::<Vec<_>>();
     compiled.sort();
     for (cmd_long, cmd) in conf.cmds.0 {
         let mut multi_word = BTreeMap::new();
@@ -0,0 +1,133 @@
+use tracing::warn;
+
+use super::compile::{Conflict, compile_with_conflicts};
+use super::expand;
+use super::shadow::{Kind, Names};
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Problem {
+    Conflict(Conflict),
+    Shadows {
+        short: String,
+        long: String,
+        kind: Kind,
+    },
+}
+
+pub(super) fn check(conf: &expand::ConfigFile, names: &Names) -> Vec<Problem> {
+    let (compiled, conflicts) = compile_with_conflicts(&conf.cmds);
+    let mut problems = conflicts
+        .into_iter()
+        .map(Problem::Conflict)
+        .collect::<Vec<_>>();
+    for (short, long) in compiled {
+        if short.contains(' ') || long.split_whitespace().next() == Some(short.as_str()) {
+            continue;
+        }
+        if let Some(kind) = names.shadows(&short) {
+            problems.push(Problem::Shadows { short, long, kind });
+        }
+    }
+    problems
+}
+
+/// Print the keys that are bound more than once, which `zbr check` reports
+/// along with other problems
+pub(super) fn report_conflicts(conf: &expand::ConfigFile) {
+    let (_compiled, conflicts) = compile_with_conflicts(&conf.cmds);
+    for Conflict { key, first, second } in conflicts {
+        warn!(
+            "{key} is bound to both `{}` and `{}`, see `zbr check`",
+            first.trim_end(),
+            second.trim_end()
+        );
+    }
+}
+
+/// Returns whether there were any problems
+pub(super) fn go(conf: expand::ConfigFile, names: &Names) -> bool {
+    let problems = check(&conf, names);
+    for problem in &problems {
+        match problem {
+            Problem::Conflict(Conflict { key, first, second }) => println!(
+                "{key} is bound to both `{}` and `{}`",
+                first.trim_end(),
+                second.trim_end()
+            ),
+            Problem::Shadows { short, long, kind } => {
+                println!(
+                    "{short} --> {} shadows the {kind} `{short}`",
+                    long.trim_end()
+                );
+            }
+        }
+    }
+    !problems.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Problem, check};
+    use crate::zle::compile::Conflict;
+    use crate::zle::expand::ConfigFile;
+    use crate::zle::shadow::Names;
+
+    #[test]
+    fn test_check_conflict() {
+        // `cb` is both cabal and `c --bar`
+        let conf: ConfigFile = toml::from_str(
+            r#"
+            [cmds.cabal]
+            short = "cb"
+            [cmds.c]
+            short = "c"
+            [cmds.c.flags.--bar]
+            short = "b"
+            squish = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            check(&conf, &Names::default()),
+            [Problem::Conflict(Conflict {
+                key: String::from("cb"),
+                first: String::from("c --bar "),
+                second: String::from("cabal "),
+            })]
+        );
+    }
+
+    #[test]
+    fn test_check_squished_conflict() {
+        // `gsh` and `git sh` are both `git show` and the deeper
+        // `git status here`
+        let conf: ConfigFile = toml::from_str(
+            r#"
+            [cmds.git]
+            short = "g"
+            [cmds.git.subs.show]
+            short = "sh"
+            [cmds.git.subs.status]
+            short = "s"
+            [cmds.git.subs.status.subs.here]
+            short = "h"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            check(&conf, &Names::default()),
+            [
+                Problem::Conflict(Conflict {
+                    key: String::from("gsh"),
+                    first: String::from("git show "),
+                    second: String::from("git status here "),
+                }),
+                Problem::Conflict(Conflict {
+                    key: String::from("git sh"),
+                    first: String::from("git show "),
+                    second: String::from("git status here "),
+                })
+            ]
+        );
+    }
+}
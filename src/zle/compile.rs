@@ -1,41 +1,232 @@
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
 
-use tracing::{debug, warn};
+use tracing::debug;
 
 use super::abbrev::unique_prefixes;
 use super::extract::{Cmd, Cmds};
 
-pub(super) fn compile_recursive(
-    mut pfx: String,
-    cmd: &Cmd,
-    long: &str,
-    lbuf: &str,
+/// A key that was bound to two different expansions
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Conflict {
+    pub(super) key: String,
+    pub(super) first: String,
+    pub(super) second: String,
+}
+
+fn insert(m: &mut BTreeMap<String, String>, conflicts: &mut Vec<Conflict>, k: String, v: String) {
+    if let Some(existing) = m.get(k.as_str())
+        && *existing != v
+    {
+        debug!("Map already contained key! {k} -> {v}, {existing}");
+        conflicts.push(Conflict {
+            key: k.clone(),
+            first: existing.clone(),
+            second: v.clone(),
+        });
+    }
+    m.insert(k, v);
+}
+
+struct Binder<'a> {
+    m: BTreeMap<String, String>,
+    conflicts: &'a mut Vec<Conflict>,
+    lbuf: &'a str,
     all: bool,
-) -> BTreeMap<String, String> {
-    debug!("Prefix: {pfx}");
-    let mut m = BTreeMap::new();
-    let mut bind = |k: String, v: String| -> bool {
+}
+
+impl Binder<'_> {
+    fn bind(&mut self, k: String, v: String) -> bool {
         debug!("considering binding '{k}' to '{v}'");
-        debug_assert!(!k.ends_with(' '));
         debug_assert!(v.ends_with(' '));
-        if !all && !k.starts_with(lbuf) && !v.starts_with(lbuf) {
+        if k.ends_with(' ') {
+            // Typing the space would expand the part before it, e.g., for the
+            // short `e ` of `nix "env shell"`
+            debug!("not binding '{k}', it ends with a space");
+            return false;
+        }
+        if !self.all && !k.starts_with(self.lbuf) && !v.starts_with(self.lbuf) {
             // Ideally, we could avoid ever being in this case
             // warn!("Irrelevant to {lbuf}: {k} {v}");
             return false;
         }
         debug!("binding '{k}' to '{v}'");
-        if let Some(existing) = m.get(k.as_str()) {
-            warn!("Map already contained key! {k} -> {v}, {existing}");
-        }
-        m.insert(k, v);
+        insert(&mut self.m, self.conflicts, k, v);
         true
+    }
+}
+
+/// At most this many flags are squished into one abbreviation. Each squished
+/// flag that may be followed by others multiplies the number of abbreviations.
+const MAX_SQUISHED_FLAGS: usize = 2;
+
+/// A squished abbreviation that may be extended, see [`squished`]
+struct Squished<'a> {
+    key: String,
+    long: String,
+    cmd: &'a Cmd,
+    /// The last flag squished into `key` since `cmd`
+    last_flag: Option<&'a String>,
+    /// The number of flags squished into `key`
+    flags: usize,
+    depth: usize,
+}
+
+/// Squished subcommands and flags at any depth below `cmd`, e.g., `gsu` for
+/// `git submodule`, `gsuu` for `git submodule update`, and `gsuui` for
+/// `git submodule update --init`. Squished flags may be followed by further
+/// flags or subcommands, e.g., `sysus` for `systemctl --user status`, up to
+/// [`MAX_SQUISHED_FLAGS`] in all. Returns the depth of each, shallowest first.
+fn squished(
+    key: &str,
+    long: &str,
+    cmd: &Cmd,
+    lbuf: &str,
+    all: bool,
+) -> Vec<(String, String, usize)> {
+    let mut r = Vec::new();
+    let mut queue = VecDeque::from([Squished {
+        key: String::from(key),
+        long: String::from(long),
+        cmd,
+        last_flag: None,
+        flags: 0,
+        depth: 1,
+    }]);
+    while let Some(s) = queue.pop_front() {
+        for (f, fl) in BTreeMap::from_iter(&s.cmd.flags) {
+            // Squish each flag at most once, in order
+            if !fl.squish || s.flags >= MAX_SQUISHED_FLAGS || s.last_flag.is_some_and(|l| l >= f) {
+                continue;
+            }
+            let k = format!("{}{}", s.key, fl.short);
+            let l = format!("{} {f}", s.long);
+            r.push((k.clone(), format!("{l} "), s.depth));
+            // e.g., `sysus` for `systemctl --user status`
+            if all || lbuf.starts_with(&k) {
+                queue.push_back(Squished {
+                    key: k,
+                    long: l,
+                    cmd: s.cmd,
+                    last_flag: Some(f),
+                    flags: s.flags + 1,
+                    depth: s.depth + 1,
+                });
+            }
+        }
+        for (sub_long, sub) in s.cmd.subs.enabled() {
+            let k = format!("{}{}", s.key, sub.short);
+            let l = format!("{} {sub_long}", s.long);
+            r.push((k.clone(), format!("{l} "), s.depth));
+            if all || lbuf.starts_with(&k) {
+                queue.push_back(Squished {
+                    key: k,
+                    long: l,
+                    cmd: sub,
+                    last_flag: None,
+                    flags: s.flags,
+                    depth: s.depth + 1,
+                });
+            }
+        }
+    }
+    r
+}
+
+/// `long` followed by anything already typed in `lbuf` that may precede a
+/// subcommand of `cmd`: flags and their values, e.g., `git -C ../other` for
+/// `git -C ../other co`, and toolchain selectors, e.g., `cargo +nightly` for
+/// `cargo +nightly b`.
+fn with_typed_flags(long: String, cmd: &Cmd, lbuf: &str) -> String {
+    let mut r = long;
+    let Some(rest) = lbuf
+        .strip_prefix(r.as_str())
+        .and_then(|s| s.strip_prefix(' '))
+    else {
+        return r;
+    };
+    // Only words that are followed by a space are complete
+    let mut words = rest.split(' ').collect::<Vec<_>>();
+    words.pop();
+    let mut words = words.into_iter().take_while(|w| !w.is_empty());
+    while let Some(word) = words.next() {
+        let arity = if word.starts_with('+') {
+            0
+        } else if let Some((name, _value)) = word.split_once('=')
+            && word.starts_with('-')
+            && cmd.flag(name).is_some()
+        {
+            0
+        } else if let Some(flag) = cmd.flag(word) {
+            flag.arity
+        } else {
+            break;
+        };
+        let values = words.by_ref().take(arity).collect::<Vec<_>>();
+        if values.len() < arity {
+            break;
+        }
+        r.push(' ');
+        r.push_str(word);
+        for value in values {
+            r.push(' ');
+            r.push_str(value);
+        }
+    }
+    r
+}
+
+/// Unique prefixes of the values of `cmd` and its flags, when they are being
+/// typed, e.g., `rustup toolchain install n` for `rustup toolchain install
+/// nightly`. `prefix` is `cmd` and any flags typed so far.
+fn bind_values(b: &mut Binder<'_>, prefix: &str, cmd: &Cmd) {
+    let mut sets = Vec::new();
+    if let Some(values) = &cmd.values {
+        // Don't shadow subcommands, which go in the same position
+        let deny = HashSet::from_iter(cmd.subs.0.values().map(|s| s.short.as_str()));
+        sets.push((String::from(prefix), values, deny));
+    }
+    for (f, fl) in &cmd.flags {
+        if let Some(values) = &fl.values {
+            for name in std::iter::once(f).chain(&fl.alias) {
+                sets.push((format!("{prefix} {name}"), values, HashSet::new()));
+            }
+        }
+    }
+    for (pfx, values, deny) in sets {
+        let Some(rest) = b.lbuf.strip_prefix(pfx.as_str()) else {
+            continue;
+        };
+        if !rest.starts_with(' ') || rest[1..].contains(' ') {
+            continue;
+        }
+        let values = values.get();
+        for (v, p) in unique_prefixes(&values, &deny) {
+            b.bind(format!("{pfx} {p}"), format!("{pfx} {v} "));
+        }
+    }
+}
+
+pub(super) fn compile_recursive(
+    mut pfx: String,
+    cmd: &Cmd,
+    long: &str,
+    lbuf: &str,
+    all: bool,
+    conflicts: &mut Vec<Conflict>,
+) -> BTreeMap<String, String> {
+    debug!("Prefix: {pfx}");
+    let mut b = Binder {
+        m: BTreeMap::new(),
+        conflicts,
+        lbuf,
+        all,
     };
 
     if !pfx.is_empty() && !pfx.ends_with(' ') {
         pfx.push(' ');
     }
     if !all && pfx.len() > lbuf.len() + 1 {
-        return m;
+        return b.m;
     }
 
     // Shouldn't have made the recursive call if this isn't the case
@@ -52,77 +243,87 @@ pub(super) fn compile_recursive(
         && doesnt_start_with_prefix
     {
         // warn!("Irrelevant to {lbuf}: {pre_short} {pre_long}");
-        return m;
+        return b.m;
     }
     debug!("binding root");
     pre_long.push(' ');
-    bind(pre_short, pre_long);
+    b.bind(pre_short.clone(), pre_long);
 
     for (f, fl) in &cmd.flags {
         let expanded = format!("{pfx}{long} {f} ");
-        bind(format!("{pfx}{long} -{}", fl.short), expanded.clone());
-        if cmd.no_args {
-            bind(format!("{pfx}{long} {}", fl.short), expanded.clone());
-        }
-        if fl.squish {
-            bind(format!("{pfx}{short}{}", fl.short), expanded);
+        b.bind(format!("{pfx}{long} -{}", fl.short), expanded.clone());
+        if cmd.no_args == Some(true) {
+            b.bind(format!("{pfx}{long} {}", fl.short), expanded);
         }
     }
-    for (sub_long, sub) in &cmd.subs.0 {
-        let sub_short = &sub.short;
-        debug!("binding sub: {sub_long}");
-
-        let k = format!("{pfx}{short}{sub_short}");
-        let starts_with_key = lbuf.starts_with(&k);
-        bind(k, format!("{pfx}{long} {sub_long} "));
-        if !starts_with_key {
-            continue;
-        }
-
-        // e.g., bind `gsuu` to `git submodule update`
-        // TODO: Do this recursively
-        for (sub_sub_long, sub_sub) in &sub.subs.0 {
-            let k = format!("{sub_short}{}", sub_sub.short);
-            if !cmd.subs.0.contains_key(&k) {
-                bind(
-                    format!("{pfx}{short}{k}"),
-                    format!("{pfx}{long} {sub_long} {sub_sub_long} "),
-                );
-            }
-        }
-        for (f, fl) in &sub.flags {
-            if fl.squish {
-                bind(
-                    format!("{pfx}{short}{sub_short}{}", fl.short),
-                    format!("{pfx}{long} {sub_long} {f} "),
-                );
+    // e.g., bind `gsu` to `git submodule` and `gsuu` to `git submodule update`
+    for (k, v, depth) in squished(&pre_short, &format!("{pfx}{long}"), cmd, lbuf, all) {
+        // Don't let deeper squished forms shadow shallower ones
+        if depth > 1
+            && let Some(existing) = b.m.get(&k)
+        {
+            debug!("not binding '{k}' to '{v}', it's already bound");
+            if *existing != v {
+                b.conflicts.push(Conflict {
+                    key: k,
+                    first: existing.clone(),
+                    second: v,
+                });
             }
+            continue;
         }
+        b.bind(k, v);
     }
-    for (sub_long, sub) in &cmd.subs.0 {
+    let prefix = if all {
+        format!("{pfx}{long}")
+    } else {
+        with_typed_flags(format!("{pfx}{long}"), cmd, lbuf)
+    };
+    if !all && !doesnt_start_with_prefix {
+        bind_values(&mut b, &prefix, cmd);
+    }
+    for (sub_long, sub) in cmd.subs.enabled() {
         debug!("considering binding sub: {sub_long}");
         if !all && doesnt_start_with_prefix {
             continue;
         }
         debug!("binding sub: {sub_long}");
-        let prefix = format!("{pfx}{long}");
-        for (short, long) in compile_recursive(prefix, sub, sub_long, lbuf, all) {
-            bind(short, long);
+        let prefix = prefix.clone();
+        for (short, long) in compile_recursive(prefix, sub, sub_long, lbuf, all, b.conflicts) {
+            b.bind(short, long);
         }
     }
-    m
+    b.m
 }
 
-pub(super) fn compile(cmds: &Cmds, lbuf: &str, all: bool) -> BTreeMap<String, String> {
+fn compile_into(
+    cmds: &Cmds,
+    lbuf: &str,
+    all: bool,
+    conflicts: &mut Vec<Conflict>,
+) -> BTreeMap<String, String> {
     let mut r = BTreeMap::new();
-    for (long, cmd) in &cmds.0 {
+    for (long, cmd) in cmds.enabled() {
         if lbuf.is_empty() || lbuf.starts_with(&cmd.short) || lbuf.starts_with(long) {
-            r.extend(compile_recursive(String::new(), cmd, long, lbuf, all));
+            for (k, v) in compile_recursive(String::new(), cmd, long, lbuf, all, conflicts) {
+                insert(&mut r, conflicts, k, v);
+            }
         }
     }
     r
 }
 
+pub(super) fn compile(cmds: &Cmds, lbuf: &str, all: bool) -> BTreeMap<String, String> {
+    compile_into(cmds, lbuf, all, &mut Vec::new())
+}
+
+/// Compile all abbreviations, and find keys that are bound more than once.
+pub(super) fn compile_with_conflicts(cmds: &Cmds) -> (BTreeMap<String, String>, Vec<Conflict>) {
+    let mut conflicts = Vec::new();
+    let compiled = compile_into(cmds, "", true, &mut conflicts);
+    (add_prefixes(compiled, "", true), conflicts)
+}
+
 pub(super) fn compile_with_prefixes(
     cmds: &Cmds,
     lbuf: &str,
@@ -178,7 +379,9 @@ fn add_prefixes(
 #[cfg(test)]
 mod tests {
     use std::collections::{BTreeMap, HashMap};
+    use std::fmt::Write as _;
 
+    use crate::zle::expand::ConfigFile;
     use crate::zle::extract::{Cmd, Cmds};
 
     use super::compile;
@@ -192,17 +395,23 @@ mod tests {
             String::from("git"),
             Cmd {
                 short: String::from("g"),
+                desc: None,
                 flags: HashMap::new(),
-                no_args: false,
+                no_args: None,
                 subs: Cmds(BTreeMap::from([(
                     String::from("submodule"),
                     Cmd {
                         short: String::from("su"),
+                        desc: None,
                         flags: HashMap::new(),
-                        no_args: false,
+                        no_args: None,
                         subs: Cmds::default(),
+                        values: None,
+                        disable: None,
                     },
                 )])),
+                values: None,
+                disable: None,
             },
         )]));
         assert_eq!(
@@ -213,4 +422,25 @@ mod tests {
             [("g", "git "), ("gsu", "git submodule ")]
         );
     }
+
+    #[test]
+    fn test_compile_squished_flags() {
+        // 16 squishable flags could be squished in 2^16 combinations before
+        // each subcommand
+        let mut toml = String::from("[cmds.x]\nshort = \"x\"\n");
+        for c in 'a'..='p' {
+            let _ = writeln!(toml, "[cmds.x.flags.--{c}]\nshort = \"{c}\"\nsquish = true");
+        }
+        for c in 'A'..='T' {
+            let _ = writeln!(toml, "[cmds.x.subs.{c}]\nshort = \"{c}\"");
+        }
+        let conf = toml::from_str::<ConfigFile>(&toml).unwrap();
+        let compiled = compile(&conf.cmds, "", true);
+        assert!(compiled.len() < 10_000, "{} bindings", compiled.len());
+        assert_eq!(
+            compiled.get("xabA").map(String::as_str),
+            Some("x --a --b A ")
+        );
+        assert_eq!(compiled.get("xabcA"), None);
+    }
 }
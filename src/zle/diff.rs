@@ -0,0 +1,100 @@
+use super::compile::compile_with_prefixes;
+use super::expand;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Change {
+    Added(String, String),
+    Removed(String, String),
+    Changed(String, String, String),
+}
+
+pub(super) fn diff(old: &expand::ConfigFile, new: &expand::ConfigFile) -> Vec<Change> {
+    let old = compile_with_prefixes(&old.cmds, "", true);
+    let mut new = compile_with_prefixes(&new.cmds, "", true);
+    let mut changes = Vec::new();
+    for (short, old_long) in old {
+        match new.remove(&short) {
+            None => changes.push(Change::Removed(short, old_long)),
+            Some(new_long) if new_long != old_long => {
+                changes.push(Change::Changed(short, old_long, new_long));
+            }
+            Some(_) => (),
+        }
+    }
+    changes.extend(
+        new.into_iter()
+            .map(|(short, long)| Change::Added(short, long)),
+    );
+    changes.sort_by(|c, d| key(c).cmp(key(d)));
+    changes
+}
+
+fn key(c: &Change) -> &str {
+    match c {
+        Change::Added(k, _) | Change::Removed(k, _) | Change::Changed(k, _, _) => k,
+    }
+}
+
+pub(super) fn go(old: expand::ConfigFile, new: expand::ConfigFile) {
+    for change in diff(&old, &new) {
+        match change {
+            Change::Added(k, v) => println!("+ {k} --> {}", v.trim_end()),
+            Change::Removed(k, v) => println!("- {k} --> {}", v.trim_end()),
+            Change::Changed(k, o, n) => {
+                println!("~ {k} --> {} (was {})", n.trim_end(), o.trim_end());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, diff, key};
+    use crate::zle::expand::ConfigFile;
+
+    fn parse(s: &str) -> ConfigFile {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = parse(
+            r#"
+            [cmds.git]
+            short = "g"
+            [cmds.git.subs.submodule]
+            short = "su"
+            [cmds.git.subs.status]
+            short = "s"
+            "#,
+        );
+        assert_eq!(diff(&old, &old), []);
+
+        let new = parse(
+            r#"
+            [cmds.git]
+            short = "g"
+            [cmds.git.subs.submodule]
+            short = "sm"
+            [cmds.git.subs.status]
+            short = "su"
+            "#,
+        );
+        let changes = diff(&old, &new)
+            .into_iter()
+            .filter(|c| !key(c).contains(' '))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            [
+                Change::Removed(String::from("gs"), String::from("git status ")),
+                Change::Added(String::from("gsm"), String::from("git submodule ")),
+                Change::Changed(
+                    String::from("gsu"),
+                    String::from("git submodule "),
+                    String::from("git status ")
+                ),
+            ]
+        );
+    }
+}
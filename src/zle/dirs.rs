@@ -0,0 +1,224 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs};
+
+use tracing::debug;
+
+/// `$<var>/zbr`, falling back to `~/<fallback>/zbr`
+fn xdg(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(fallback)))
+        .map(|d| d.join("zbr"))
+}
+
+/// `$XDG_CACHE_HOME/zbr`, or `~/.cache/zbr`
+pub(super) fn cache_dir() -> Option<PathBuf> {
+    xdg("XDG_CACHE_HOME", ".cache")
+}
+
+/// `$XDG_CONFIG_HOME/zbr`, or `~/.config/zbr`
+pub(super) fn config_dir() -> Option<PathBuf> {
+    xdg("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_STATE_HOME/zbr`, or `~/.local/state/zbr`
+pub(super) fn state_dir() -> Option<PathBuf> {
+    xdg("XDG_STATE_HOME", ".local/state")
+}
+
+/// The most caches of each kind to keep, see [`prune`]
+const MAX_CACHES: usize = 64;
+
+/// A file in the [`cache_dir`] whose first line is a key, e.g., the command
+/// that computed the rest, or the [`stamp`] of the file it was parsed from.
+/// The rest only applies while the key matches. Only the [`MAX_CACHES`] most
+/// recently written caches of each kind are kept.
+#[derive(Debug)]
+pub(super) struct Cache {
+    path: PathBuf,
+    key: String,
+}
+
+impl Cache {
+    /// The cache named by a hash of `name` in the `kind` subdirectory of
+    /// `dir`, usually the [`cache_dir`]
+    pub(super) fn new(dir: &Path, kind: &str, name: impl Hash, key: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        let file = format!("{:016x}", hasher.finish());
+        Self {
+            path: dir.join(kind).join(file),
+            key: key.replace('\n', " "),
+        }
+    }
+
+    /// The contents after the key, if the key matches, and if the cache was
+    /// written less than `ttl` ago
+    pub(super) fn read(&self, ttl: Option<Duration>) -> Option<String> {
+        if let Some(ttl) = ttl {
+            let age = fs::metadata(&self.path)
+                .ok()?
+                .modified()
+                .ok()
+                .and_then(|m| SystemTime::now().duration_since(m).ok())?;
+            if age > ttl {
+                return None;
+            }
+        }
+        let content = fs::read_to_string(&self.path).ok()?;
+        let (first, rest) = content.split_once('\n')?;
+        (first == self.key).then(|| String::from(rest))
+    }
+
+    pub(super) fn write(&self, content: &str) {
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&self.path, format!("{}\n{content}", self.key)));
+        if let Err(e) = result {
+            debug!("Couldn't write {}: {e}", self.path.display());
+        }
+        if let Some(dir) = self.path.parent() {
+            prune(dir, MAX_CACHES);
+        }
+    }
+}
+
+/// Remove all but the `max` most recently written caches in `dir`. Files with
+/// the same stem, e.g., `0123456789abcdef.json` and
+/// `0123456789abcdef.compiled.json`, count as one cache.
+pub(super) fn prune(dir: &Path, max: usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files = entries
+        .filter_map(Result::ok)
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .collect::<Vec<_>>();
+    files.sort_by_key(|(t, _)| std::cmp::Reverse(*t));
+    let stem = |p: &Path| {
+        let name = p.file_name().unwrap_or_default().to_string_lossy();
+        String::from(name.split('.').next().unwrap_or_default())
+    };
+    let mut keep = Vec::new();
+    for (_, path) in files {
+        let stem = stem(&path);
+        if keep.contains(&stem) {
+            continue;
+        }
+        if keep.len() < max {
+            keep.push(stem);
+        } else if let Err(e) = fs::remove_file(&path) {
+            debug!("Couldn't remove {}: {e}", path.display());
+        }
+    }
+}
+
+/// A path and its modification time, to key a [`Cache`] of its contents
+pub(super) fn stamp(path: &Path) -> String {
+    let mtime = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!("{}\t{}", path.display(), mtime.as_nanos())
+}
+
+/// A directory for a test, removed when it's dropped, even if the test fails
+#[cfg(test)]
+#[derive(Debug)]
+pub(super) struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// An empty `zbr-<name>-<pid>` in the system's temporary directory
+    pub(super) fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("zbr-{name}-{}", std::process::id()));
+        // Left over from a run that was killed
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    /// Write `content` to `name`, creating its parent directories
+    pub(super) fn write(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.0.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_dir_all(&self.0) {
+            debug!("Couldn't remove {}: {e}", self.0.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use super::{Cache, MAX_CACHES, TempDir, prune, stamp};
+
+    #[test]
+    fn test_cache() {
+        let dir = TempDir::new("cache");
+        let path = dir.join("values").join("cache");
+        let cache = |key: &str| Cache {
+            path: path.clone(),
+            key: String::from(key),
+        };
+        assert_eq!(cache("a").read(None), None);
+        cache("a").write("main\nmaster");
+        assert_eq!(cache("a").read(None).as_deref(), Some("main\nmaster"));
+        assert_eq!(cache("b").read(None), None);
+        assert_eq!(cache("a").read(Some(Duration::ZERO)), None);
+        assert_ne!(stamp(&path), stamp(&dir.join("missing")));
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = TempDir::new("prune");
+        for i in 0..MAX_CACHES + 8 {
+            Cache::new(&dir, "values", i, "key").write("main");
+        }
+        assert_eq!(
+            fs::read_dir(dir.join("values")).unwrap().count(),
+            MAX_CACHES
+        );
+        for i in 0..8 {
+            dir.write(&format!("config/{i:016x}.json"), "");
+            dir.write(&format!("config/{i:016x}.compiled.json"), "");
+        }
+        prune(&dir.join("config"), 4);
+        assert_eq!(fs::read_dir(dir.join("config")).unwrap().count(), 2 * 4);
+    }
+}
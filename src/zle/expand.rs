@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
 
@@ -7,31 +8,195 @@ use crate::build;
 
 use super::compile::compile_with_prefixes;
 use super::extract::Cmds;
+use super::project::Project;
+use super::shadow::Names;
+use super::{dirs, layers};
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Default, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
 pub(crate) struct ConfigFile {
+    /// Other configs, relative to this one. This one takes precedence over
+    /// them, and later ones over earlier ones.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) include: Vec<String>,
     #[serde(default)]
     pub(super) cmds: Cmds,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Shadowed::is_default")]
+    pub(super) shadowed: Shadowed,
+    /// Abbreviations not to expand, e.g., `gsuseb`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) disable: Vec<String>,
+    /// Abbreviations to expand to something else. These take precedence over
+    /// `disable`.
+    #[serde(default)]
+    #[serde(rename = "override")]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) overrides: BTreeMap<String, String>,
+    /// The files that this config was read from, including the included ones
+    #[serde(skip)]
+    pub(super) sources: Vec<PathBuf>,
+    /// The cached table of every abbreviation, see [`ConfigFile::compile_all`]
+    #[serde(skip)]
+    pub(super) compiled: Option<PathBuf>,
+}
+
+/// What to do with an abbreviation that is also an alias, builtin, function,
+/// or executable
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    schemars::JsonSchema,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum Policy {
+    /// Expand it anyway
+    #[default]
+    Always,
+    /// Don't expand it
+    Never,
+    /// Only expand it after a second space
+    DoubleSpace,
+}
+
+#[derive(
+    Debug, Default, PartialEq, Eq, schemars::JsonSchema, serde::Deserialize, serde::Serialize,
+)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Shadowed {
+    /// The policy for abbreviations not in `keys`
+    #[serde(default)]
+    pub(super) default: Policy,
+    /// Policies for specific abbreviations
+    #[serde(default)]
+    pub(super) keys: BTreeMap<String, Policy>,
+}
+
+impl Shadowed {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn has_double_space(&self) -> bool {
+        self.default == Policy::DoubleSpace || self.keys.values().any(|p| *p == Policy::DoubleSpace)
+    }
+
+    /// Whether to expand `key`, which was followed by a second space if
+    /// `repeated`.
+    fn allows(&self, key: &str, repeated: bool, is_shadowed: impl FnOnce() -> bool) -> bool {
+        if key.contains(' ') {
+            return !repeated;
+        }
+        match self.keys.get(key).copied().unwrap_or(self.default) {
+            Policy::Always => !repeated,
+            Policy::Never => !repeated && !is_shadowed(),
+            Policy::DoubleSpace => repeated == is_shadowed(),
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigFileError {
     #[error("i/o error for config file at {1}: {0}")]
     Io(io::Error, PathBuf),
-    #[error("toml error")]
-    Toml(#[from] toml::de::Error),
+    #[error("toml error in config file at {1}: {0}")]
+    Toml(toml::de::Error, PathBuf),
+    #[error("config file at {0} includes itself")]
+    IncludeCycle(PathBuf),
+    #[error("config file at {1} adds `{0}` without a `short`")]
+    MissingShort(String, PathBuf),
+}
+
+/// `~/` is the home directory, and other relative paths are relative to the
+/// directory of the including file.
+pub(super) fn resolve_include(including: &Path, include: &str) -> PathBuf {
+    if let Some(rest) = include.strip_prefix("~/")
+        && let Some(home) = std::env::var_os("HOME")
+    {
+        return PathBuf::from(home).join(rest);
+    }
+    including
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(include)
 }
 
 impl ConfigFile {
     pub(super) fn from_file<P: AsRef<Path>>(p: P) -> Result<Self, ConfigFileError> {
-        let path = p.as_ref();
+        Self::from_file_including(p.as_ref(), &mut Vec::new())
+    }
+
+    /// Read one config, without the configs it includes
+    pub(super) fn read(path: &Path) -> Result<Self, ConfigFileError> {
         let s = fs::read_to_string(path).map_err(|e| ConfigFileError::Io(e, path.to_path_buf()))?;
-        Ok(toml::from_str::<ConfigFile>(&s)?)
+        let mut conf = toml::from_str::<ConfigFile>(&s)
+            .map_err(|e| ConfigFileError::Toml(e, path.to_path_buf()))?;
+        conf.sources.push(path.to_path_buf());
+        Ok(conf)
+    }
+
+    /// `stack` holds the files that (transitively) include this one.
+    fn from_file_including(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Self, ConfigFileError> {
+        let io_err = |e| ConfigFileError::Io(e, path.to_path_buf());
+        let mut conf = Self::read(path)?;
+        if conf.include.is_empty() {
+            return Ok(conf);
+        }
+        let canonical = path.canonicalize().map_err(io_err)?;
+        if stack.contains(&canonical) {
+            return Err(ConfigFileError::IncludeCycle(canonical));
+        }
+        stack.push(canonical);
+        let mut merged = ConfigFile::default();
+        for include in std::mem::take(&mut conf.include) {
+            let included = Self::from_file_including(&resolve_include(path, &include), stack)?;
+            layers::merge(&mut merged, included);
+        }
+        stack.pop();
+        layers::merge(&mut merged, conf);
+        Ok(merged)
+    }
+
+    /// Like [`compile_with_prefixes`], with `disable` and `override` applied
+    pub(super) fn compile(&self, lbuf: &str, all: bool) -> BTreeMap<String, String> {
+        let mut compiled = compile_with_prefixes(&self.cmds, lbuf, all);
+        for key in &self.disable {
+            compiled.remove(key);
+        }
+        compiled.extend(self.overrides.clone());
+        compiled
+    }
+
+    /// Like [`ConfigFile::compile`] with every abbreviation, but read from the
+    /// table that was cached when the config was loaded, if there is one. The
+    /// cached table doesn't have the abbreviations from [`Self::add_project`].
+    pub(super) fn compile_all(&self) -> BTreeMap<String, String> {
+        self.compiled
+            .as_ref()
+            .and_then(|c| fs::read_to_string(c).ok())
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_else(|| self.compile("", true))
+    }
+
+    /// Add abbreviations derived from the project in the current directory,
+    /// see [`Project`].
+    pub(super) fn add_project(&mut self) {
+        if let Ok(pwd) = std::env::current_dir() {
+            Project::detect(&pwd, dirs::cache_dir().as_deref()).merge(&mut self.cmds);
+        }
     }
 }
 
-fn expand_pre(conf: ConfigFile, lbuf: String) -> Option<String> {
-    let compiled = compile_with_prefixes(&conf.cmds, &lbuf, false);
+fn expand_pre(conf: &ConfigFile, lbuf: String) -> Option<String> {
+    let compiled = conf.compile(&lbuf, false);
     if let Some(r) = compiled.get(lbuf.as_str()) {
         debug!("Expanding {lbuf} to {r}");
         return Some(r.clone());
@@ -107,28 +272,157 @@ pub(super) fn clean_buf(mut lbuf: String) -> (String, String) {
     (prefix, lbuf)
 }
 
-pub(crate) fn expand(conf: ConfigFile, lbuf: String, rbuf: String) -> Option<String> {
+/// `shadowed` says whether the abbreviation is also the name of something zsh
+/// could run. If it's not provided, it's computed from a cached snapshot.
+pub(crate) fn expand(
+    conf: ConfigFile,
+    lbuf: String,
+    rbuf: String,
+    shadowed: Option<bool>,
+) -> Option<String> {
     if !rbuf.is_empty() {
         return None;
     }
     let (prefix, lbuf) = clean_buf(lbuf);
-    let expanded = expand_pre(conf, lbuf);
+    // A second space after an abbreviation that wasn't expanded
+    let (lbuf, repeated) = match lbuf.strip_suffix(' ') {
+        Some(l) => (String::from(l), true),
+        None => (lbuf, false),
+    };
+    if repeated && !conf.shadowed.has_double_space() {
+        return None;
+    }
+    let expanded = expand_pre(&conf, lbuf.clone());
     debug!("expanded = {expanded:?}");
+    let is_shadowed = || shadowed.unwrap_or_else(|| Names::cached().shadows(&lbuf).is_some());
+    if expanded.is_some() && !conf.shadowed.allows(&lbuf, repeated, is_shadowed) {
+        debug!("Not expanding {lbuf}, which shadows a command");
+        return None;
+    }
     expanded.map(|s| format!("{prefix}{s}"))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::expand;
+    use super::{ConfigFile, ConfigFileError, expand};
+    use crate::zle::dirs::TempDir;
 
     fn test_expand(l: &str, r: &str) {
-        let conf = super::ConfigFile::from_file("conf/conf.toml").unwrap();
+        let conf = ConfigFile::from_file("conf/conf.toml").unwrap();
         assert_eq!(
-            expand(conf, String::from(l), String::new()).as_deref(),
+            expand(conf, String::from(l), String::new(), None).as_deref(),
             Some(r)
         );
     }
 
+    #[test]
+    fn test_disable_override() {
+        let conf = || -> ConfigFile {
+            toml::from_str(
+                r#"
+                disable = ["gs", "git s"]
+                override = { gp = "git push --force-with-lease ", gs = "git switch " }
+                [cmds.git]
+                short = "g"
+                [cmds.git.subs.push]
+                short = "p"
+                [cmds.git.subs.status]
+                short = "s"
+                [cmds.git.subs.stash]
+                short = "sh"
+                "#,
+            )
+            .unwrap()
+        };
+        let go = |l: &str| expand(conf(), String::from(l), String::new(), Some(false));
+        assert_eq!(go("gp").as_deref(), Some("git push --force-with-lease "));
+        assert_eq!(go("gs").as_deref(), Some("git switch "));
+        assert_eq!(go("git s").as_deref(), None);
+        assert_eq!(go("gsh").as_deref(), Some("git stash "));
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = TempDir::new("include");
+        let write = |name: &str, content: &str| dir.write(name, content);
+        let conf = write(
+            "conf.toml",
+            r#"
+            include = ["git.toml", "more/cargo.toml"]
+            [cmds.git.subs.stash]
+            short = "sh"
+            "#,
+        );
+        write(
+            "git.toml",
+            r#"
+            [cmds.git]
+            short = "g"
+            [cmds.git.subs.status]
+            short = "s"
+            [cmds.git.subs.stash]
+            short = "st"
+            "#,
+        );
+        write(
+            "more/cargo.toml",
+            r#"
+            include = ["../git.toml"]
+            [cmds.cargo]
+            short = "ca"
+            [cmds.git.subs.status]
+            short = "ss"
+            "#,
+        );
+        let go = |l: &str| {
+            let conf = ConfigFile::from_file(&conf).unwrap();
+            expand(conf, String::from(l), String::new(), Some(false))
+        };
+        assert_eq!(go("gss").as_deref(), Some("git status "));
+        assert_eq!(go("gsh").as_deref(), Some("git stash "));
+        assert_eq!(go("ca").as_deref(), Some("cargo "));
+
+        write("git.toml", r#"include = ["conf.toml"]"#);
+        assert!(matches!(
+            ConfigFile::from_file(&conf),
+            Err(ConfigFileError::IncludeCycle(_))
+        ));
+    }
+
+    #[test]
+    fn test_expand_shadowed() {
+        let conf = |policy: &str| -> ConfigFile {
+            toml::from_str(&format!(
+                r#"
+                [shadowed]
+                default = "{policy}"
+                [cmds.git]
+                short = "g"
+                [cmds.git.subs.status]
+                short = "s"
+                "#
+            ))
+            .unwrap()
+        };
+        let go = |policy: &str, l: &str, shadowed: bool| {
+            expand(conf(policy), String::from(l), String::new(), Some(shadowed))
+        };
+        let gs = Some(String::from("git status "));
+        assert_eq!(go("always", "gs", true), gs);
+        assert_eq!(go("always", "gs ", true), None);
+        assert_eq!(go("never", "gs", true), None);
+        assert_eq!(go("never", "gs", false), gs);
+        assert_eq!(go("double-space", "gs", true), None);
+        assert_eq!(go("double-space", "gs ", true), gs);
+        assert_eq!(go("double-space", "gs", false), gs);
+        assert_eq!(go("double-space", "gs ", false), None);
+        // Only the first word can shadow a command
+        assert_eq!(
+            go("never", "git s", true),
+            Some(String::from("git status "))
+        );
+    }
+
     #[test]
     fn test_expand_cargo_doc_o() {
         test_expand("cargo doc o", "cargo doc --open ");
@@ -146,8 +440,12 @@ mod tests {
 
     #[test]
     fn test_expand_gsuui() {
-        // TODO
-        // test_expand("gsuui", "git submodule update --init");
+        test_expand("gsuui", "git submodule update --init ");
+    }
+
+    #[test]
+    fn test_expand_gshum() {
+        test_expand("gshum", "git stash push --message ");
     }
 
     #[test]
@@ -155,9 +453,140 @@ mod tests {
         test_expand("git commit -m", "git commit --message ");
     }
 
-    // TODO: Squished flags before subcommands
     #[test]
     fn test_expand_sysus() {
-        // test_expand("sysus", "systemctl --user status");
+        // `systemctl` isn't in `conf/conf.toml`, see `conf/systemctl.toml`
+        let conf = || -> ConfigFile {
+            toml::from_str(
+                r#"
+                [cmds.systemctl]
+                short = "sys"
+                [cmds.systemctl.flags.--user]
+                short = "u"
+                squish = true
+                [cmds.systemctl.subs.status]
+                short = "s"
+                "#,
+            )
+            .unwrap()
+        };
+        let go = |l: &str| expand(conf(), String::from(l), String::new(), Some(false));
+        assert_eq!(go("sysu").as_deref(), Some("systemctl --user "));
+        assert_eq!(go("sysus").as_deref(), Some("systemctl --user status "));
+        assert_eq!(
+            go("systemctl --user s").as_deref(),
+            Some("systemctl --user status ")
+        );
+    }
+
+    #[test]
+    fn test_expand_after_values() {
+        let conf = || -> ConfigFile {
+            toml::from_str(
+                r#"
+                [cmds.cargo]
+                short = "ca"
+                [cmds.cargo.subs.build]
+                short = "b"
+                [cmds.docker]
+                short = "d"
+                [cmds.docker.subs.compose]
+                short = "cop"
+                [cmds.docker.subs.compose.flags.--file]
+                short = "f"
+                arity = 1
+                alias = "-f"
+                [cmds.docker.subs.compose.subs.up]
+                short = "u"
+                [cmds.git]
+                short = "g"
+                [cmds.git.flags.-C]
+                short = "C"
+                arity = 1
+                [cmds.git.subs.checkout]
+                short = "co"
+                "#,
+            )
+            .unwrap()
+        };
+        let go = |l: &str| expand(conf(), String::from(l), String::new(), Some(false));
+        assert_eq!(
+            go("git -C ../other co").as_deref(),
+            Some("git -C ../other checkout ")
+        );
+        assert_eq!(
+            go("docker compose -f x.yml u").as_deref(),
+            Some("docker compose -f x.yml up ")
+        );
+        assert_eq!(
+            go("docker compose --file=x.yml u").as_deref(),
+            Some("docker compose --file=x.yml up ")
+        );
+        assert_eq!(
+            go("cargo +nightly b").as_deref(),
+            Some("cargo +nightly build ")
+        );
+        // `-C` needs a value
+        assert_eq!(go("git -C co").as_deref(), None);
+    }
+
+    #[test]
+    fn test_expand_values() {
+        let conf = || -> ConfigFile {
+            toml::from_str(
+                r#"
+                [cmds.cargo]
+                short = "ca"
+                [cmds.cargo.subs.build]
+                short = "b"
+                [cmds.cargo.subs.build.flags.--package]
+                short = "p"
+                arity = 1
+                alias = "-p"
+                values = ["zbr", "zbr-macros", "serde"]
+                [cmds.rustup]
+                short = "ru"
+                [cmds.rustup.subs.toolchain]
+                short = "t"
+                [cmds.rustup.subs.toolchain.subs.install]
+                short = "i"
+                values = ["stable", "nightly", "list"]
+                [cmds.rustup.subs.toolchain.subs.install.subs.list]
+                short = "l"
+                "#,
+            )
+            .unwrap()
+        };
+        let go = |l: &str| expand(conf(), String::from(l), String::new(), Some(false));
+        assert_eq!(
+            go("rustup toolchain install n").as_deref(),
+            Some("rustup toolchain install nightly ")
+        );
+        assert_eq!(
+            go("rustup toolchain install st").as_deref(),
+            Some("rustup toolchain install stable ")
+        );
+        // Subcommands take precedence
+        assert_eq!(
+            go("rustup toolchain install l").as_deref(),
+            Some("rustup toolchain install list ")
+        );
+        assert_eq!(
+            go("cargo build -p s").as_deref(),
+            Some("cargo build -p serde ")
+        );
+        assert_eq!(
+            go("cargo build --package zbr-").as_deref(),
+            Some("cargo build --package zbr-macros ")
+        );
+    }
+
+    #[test]
+    fn test_expand_cargo_verbose_b() {
+        test_expand("cargo --verbose b", "cargo --verbose build ");
+        test_expand(
+            "cargo --verbose --locked b",
+            "cargo --verbose --locked build ",
+        );
     }
 }
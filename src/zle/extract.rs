@@ -1,19 +1,69 @@
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
+    io,
+    num::NonZero,
     path::{Path, PathBuf},
-    process::Command,
+    sync::{Mutex, PoisonError},
+    thread,
+    time::Duration,
 };
 
 use tracing::{debug, warn};
 
 use crate::zle::abbrev;
+use crate::zle::compile::{Conflict, compile_with_conflicts};
+use crate::zle::expand::ConfigFileError;
+use crate::zle::values::Values;
+
+mod help;
+mod manifest;
+mod pool;
+
+use self::help::Helper;
+use self::manifest::Manifest;
 
 #[derive(Debug, clap::Parser)]
 pub struct Config {
-    cmd: String,
+    #[clap(required_unless_present = "manifest", conflicts_with = "manifest")]
+    cmd: Option<String>,
     conf: Option<PathBuf>,
+    /// Extract all of the tools listed in this manifest
     #[clap(long)]
+    manifest: Option<PathBuf>,
+    #[clap(long, conflicts_with = "manifest")]
     print_subs: bool,
+    #[clap(flatten)]
+    opts: Options,
+}
+
+#[derive(Debug, clap::Parser)]
+pub struct Build {
+    /// Manifest listing the tools to extract
+    manifest: PathBuf,
+    #[clap(flatten)]
+    opts: Options,
+}
+
+#[derive(Debug, clap::Args)]
+struct Options {
+    /// Number of `--help` invocations to run at once
+    #[clap(long, short = 'j')]
+    jobs: Option<usize>,
+    /// Keep the abbreviations from a previously generated config
+    #[clap(long)]
+    previous: Option<PathBuf>,
+    /// Seconds to wait for each `--help` invocation
+    #[clap(long, default_value_t = 10)]
+    timeout: u64,
+    /// Use stderr when `--help` fails or prints nothing to stdout
+    #[clap(long)]
+    stderr: bool,
+    /// Save the output of each `--help` invocation to this directory
+    #[clap(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Read the output of `--help` from a directory made with `--record`
+    #[clap(long)]
+    replay: Option<PathBuf>,
 }
 
 /// For use with serde's [serialize_with] attribute
@@ -25,28 +75,80 @@ fn ordered_map<S: serde::Serializer, K: Ord + serde::Serialize, V: serde::Serial
     serde::Serialize::serialize(&ordered, serializer)
 }
 
-#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Default, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
 pub(super) struct Cmds(pub(super) BTreeMap<String, Cmd>);
 
 impl Cmds {
     fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+
+    /// Commands that aren't disabled
+    pub(super) fn enabled(&self) -> impl Iterator<Item = (&String, &Cmd)> {
+        self.0.iter().filter(|(_, c)| c.disable != Some(true))
+    }
+
+    /// Find the description of the last subcommand or flag in `long`, e.g.,
+    /// `git submodule update --init`.
+    pub(super) fn describe(&self, long: &str) -> Option<&str> {
+        let mut words = long.split_whitespace();
+        let mut cmd = self.0.get(words.next()?)?;
+        let mut desc = cmd.desc.as_deref();
+        for word in words {
+            if word.starts_with('-') {
+                desc = cmd.flags.get(word)?.desc.as_deref();
+            } else {
+                cmd = cmd.subs.0.get(word)?;
+                desc = cmd.desc.as_deref();
+            }
+        }
+        desc
+    }
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, schemars::JsonSchema, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
 pub(super) struct Cmd {
+    /// The abbreviation. Empty in layers that only override other fields.
+    #[serde(default)]
     pub(super) short: String,
+    /// Shown in hints
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) desc: Option<String>,
+    /// Flags, by their long names, e.g., `--verbose`
     #[serde(default)]
     #[serde(skip_serializing_if = "HashMap::is_empty")]
     #[serde(serialize_with = "ordered_map")]
     pub(super) flags: HashMap<String, Flag>,
+    /// The command takes no positional arguments, so the shorts of its flags
+    /// can be typed without a `-`
     #[serde(default)]
-    #[serde(skip_serializing_if = "is_default")]
-    pub(super) no_args: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) no_args: Option<bool>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Cmds::is_empty")]
     pub(super) subs: Cmds,
+    /// Values of the positional argument, e.g., units for `systemctl start`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) values: Option<Values>,
+    /// Remove this command from the layers below this one, or with `false`,
+    /// restore a command that a lower layer removed
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) disable: Option<bool>,
+}
+
+impl Cmd {
+    /// Find a flag by its long name or its alias.
+    pub(super) fn flag(&self, name: &str) -> Option<&Flag> {
+        self.flags.get(name).or_else(|| {
+            self.flags
+                .values()
+                .find(|f| f.alias.as_deref() == Some(name))
+        })
+    }
 }
 
 fn is_default<T: Default + PartialEq>(t: &T) -> bool {
@@ -54,44 +156,147 @@ fn is_default<T: Default + PartialEq>(t: &T) -> bool {
 }
 
 #[derive(
-    Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd, serde::Deserialize, serde::Serialize,
+    Clone,
+    Debug,
+    Default,
+    Eq,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    schemars::JsonSchema,
+    serde::Deserialize,
+    serde::Serialize,
 )]
+#[serde(deny_unknown_fields)]
 pub(super) struct Flag {
+    /// The abbreviation, typed after a `-`
     pub(super) short: String,
+    /// Shown in hints
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) desc: Option<String>,
+    /// The short can be squished together with the shorts of the command and
+    /// of its subcommands, e.g., `sysus` for `systemctl --user status`
     #[serde(default)]
     #[serde(skip_serializing_if = "is_default")]
     pub(super) squish: bool,
+    /// How many values follow the flag, e.g., 1 for `--message <msg>`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "is_default")]
+    pub(super) arity: usize,
+    /// Single-dash spelling of the flag, e.g., `-m` for `--message`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) alias: Option<String>,
+    /// Values of the flag, e.g., crates for `cargo build --package`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) values: Option<Values>,
+}
+
+/// A flag in an extraction config, see [`Flag`]
+#[derive(Clone, Debug, Default, schemars::JsonSchema, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FlagConfig {
+    /// The abbreviation, typed after a `-`
+    short: String,
+    /// Shown in hints, instead of the description in the help text
+    desc: Option<String>,
+    /// The short can be squished together with the shorts of the command and
+    /// of its subcommands
+    #[serde(default)]
+    squish: bool,
+    /// How many values follow the flag, instead of the number guessed from the
+    /// help text. May be 0.
+    arity: Option<usize>,
+    /// Single-dash spelling of the flag, e.g., `-m` for `--message`
+    alias: Option<String>,
+    /// Values of the flag
+    values: Option<Values>,
 }
 
-#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[derive(Clone, Debug, Default, schemars::JsonSchema, serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 pub(crate) struct ConfigFile {
+    /// The abbreviation, instead of one chosen automatically
     short: Option<String>,
+    /// Remove vowels from names before choosing abbreviations
     #[serde(default)]
     devowel: bool,
+    /// Abbreviations not to use
     #[serde(default)]
     deny: Vec<String>,
+    /// Find flags in the help text
     #[serde(default)]
     extract_flags: bool,
+    /// Find subcommands in the help text
     #[serde(default)]
     extract_subs: bool,
+    /// Exactly these subcommands, instead of those in the help text
     #[serde(default)]
     exact_subs: Vec<String>,
+    /// Subcommands that are missing from the help text
     #[serde(default)]
     extra_subs: Vec<String>,
+    /// Flags with fixed abbreviations, by their long names
     #[serde(default)]
-    flags: HashMap<String, Flag>,
+    flags: HashMap<String, FlagConfig>,
+    /// The command takes no positional arguments
     #[serde(default)]
     no_args: bool,
+    /// Leave out the subcommands
     #[serde(default)]
     stop: bool,
+    /// Configs for subcommands
     #[serde(default)]
     subs: HashMap<String, ConfigFile>,
+    /// Values of the positional argument
+    #[serde(default)]
+    values: Option<Values>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExtractError {
+    #[error("i/o error for extraction config at {1}: {0}")]
+    Io(io::Error, PathBuf),
+    #[error("toml error in extraction config at {1}: {0}")]
+    Toml(toml::de::Error, PathBuf),
+    #[error("invalid extraction config: {0}")]
+    InvalidConfig(String),
+    #[error("couldn't find executable `{0}`")]
+    MissingExecutable(String),
+    #[error("couldn't run `{1}`: {0}")]
+    Run(io::Error, String),
+    #[error("help text of `{0}` isn't valid UTF-8")]
+    NonUtf8(String),
+    #[error("conflicting abbreviation `{0}` for both `{1}` and `{2}`")]
+    Conflict(String, String, String),
+    #[error(transparent)]
+    Previous(#[from] ConfigFileError),
+    #[error("couldn't serialize generated config: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// The name of a flag as it's extracted from help text, e.g., `color` for
+/// `--color`, but `-C` for `-C`
+fn flag_name(long: &str) -> &str {
+    long.strip_prefix("--").unwrap_or(long)
 }
 
 impl ConfigFile {
-    pub(super) fn from_file<P: AsRef<Path>>(p: P) -> Self {
-        toml::from_str::<ConfigFile>(&std::fs::read_to_string(p).unwrap()).unwrap()
+    /// The config for a flag, by its name with or without a leading `--`
+    fn flag(&self, name: &str) -> Option<&FlagConfig> {
+        self.flags
+            .iter()
+            .find(|(long, _)| flag_name(long) == flag_name(name))
+            .map(|(_, f)| f)
+    }
+
+    pub(super) fn from_file<P: AsRef<Path>>(p: P) -> Result<Self, ExtractError> {
+        let path = p.as_ref();
+        let s =
+            std::fs::read_to_string(path).map_err(|e| ExtractError::Io(e, path.to_path_buf()))?;
+        toml::from_str::<ConfigFile>(&s).map_err(|e| ExtractError::Toml(e, path.to_path_buf()))
     }
 }
 
@@ -99,10 +304,11 @@ impl ConfigFile {
 // deconflict separately
 fn deconflict(
     conf: &ConfigFile,
+    previous: Option<&Cmd>,
     flags: &[String],
     subs: &[String],
     deny: &[String],
-) -> HashMap<String, String> {
+) -> Result<HashMap<String, String>, ExtractError> {
     debug_assert!(flags.len() == HashSet::<&String>::from_iter(flags).len());
     debug_assert!(subs.len() == HashSet::<&String>::from_iter(subs).len());
     debug_assert!(conf.flags.len() <= flags.len());
@@ -114,11 +320,15 @@ fn deconflict(
     denylist.extend(deny.iter().map(String::as_str));
 
     for flag in flags {
-        if let Some(flag_conf) = conf.flags.get(flag) {
+        if let Some(flag_conf) = conf.flag(flag) {
             let short = &flag_conf.short;
             debug_assert!(!result.contains_key(flag));
             result.insert(flag.clone(), short.clone());
-            debug_assert!(!denylist.contains(short.as_str()));
+            if denylist.contains(short.as_str()) {
+                return Err(ExtractError::InvalidConfig(format!(
+                    "short `{short}` for flag `{flag}` is already taken"
+                )));
+            }
             denylist.insert(short.as_str());
         } else {
             debug_assert!(!rest.contains(flag));
@@ -133,7 +343,11 @@ fn deconflict(
             if let Some(short) = &sub_conf.short {
                 debug_assert!(!result.contains_key(sub));
                 result.insert(sub.clone(), short.clone());
-                debug_assert!(!denylist.contains(short.as_str()));
+                if denylist.contains(short.as_str()) {
+                    return Err(ExtractError::InvalidConfig(format!(
+                        "short `{short}` for subcommand `{sub}` is already taken"
+                    )));
+                }
                 denylist.insert(short.as_str());
             } else {
                 // TODO: How to account for flags and subcommands with the same name?
@@ -149,6 +363,36 @@ fn deconflict(
     debug_assert!(rest_subs.len() == HashSet::<&String>::from_iter(rest_subs.iter()).len());
     rest.extend(rest_subs);
 
+    // Keep the shorts from a previous extraction, so that new subcommands and
+    // flags don't change existing abbreviations.
+    if let Some(prev) = previous {
+        let mut fresh = Vec::with_capacity(rest.len());
+        for long in rest {
+            let pinned = prev.subs.0.get(&long).map(|s| &s.short).or_else(|| {
+                prev.flags
+                    .get(&long)
+                    .or_else(|| prev.flags.get(&format!("--{long}")))
+                    .map(|f| &f.short)
+            });
+            match pinned {
+                // A flag and a subcommand with the same name, e.g., `cargo
+                // --version` and `cargo version`, share a short
+                Some(short) if result.get(&long) == Some(short) => (),
+                Some(short) if !denylist.contains(short.as_str()) => {
+                    debug!("Keeping previous short {short} for {long}");
+                    denylist.insert(short.as_str());
+                    result.insert(long, short.clone());
+                }
+                Some(short) => {
+                    warn!("Previous short {short} for {long} is now taken");
+                    fresh.push(long);
+                }
+                None => fresh.push(long),
+            }
+        }
+        rest = fresh;
+    }
+
     if conf.devowel {
         let rmvd = abbrev::do_remove_vowels(rest.as_slice());
         let pfxs = abbrev::unique_prefixes(&rmvd, &denylist);
@@ -167,7 +411,7 @@ fn deconflict(
                 .map(|(s, pfx)| (s, shorter_map.get(&pfx).cloned().unwrap_or(pfx.clone()))),
         );
     }
-    result
+    Ok(result)
 }
 
 fn extract_sub(words: &[&str]) -> Option<String> {
@@ -195,24 +439,50 @@ fn extract_sub(words: &[&str]) -> Option<String> {
     None
 }
 
-#[derive(Debug, Eq, Hash, PartialEq, PartialOrd)]
+/// The one-line description that follows a subcommand or option, separated
+/// from it by at least two spaces.
+fn extract_desc(line: &str) -> Option<String> {
+    let line = line.trim();
+    let idx = line.find("  ")?;
+    let desc = line[idx..].trim_start();
+    if desc.is_empty() {
+        return None;
+    }
+    Some(desc.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+#[derive(Debug, Eq, PartialEq)]
 struct Opt {
-    #[allow(dead_code)]
     short: Option<String>,
     long: String,
+    arity: usize,
 }
 
-fn extract_opt(mut words: &[&str]) -> Option<Opt> {
+/// Whether a word after an option is a placeholder for its value, e.g.,
+/// `<FILE>`, `FILE`, or `string`, rather than the start of its description or
+/// the rest of a usage line, e.g., `| -P` or `[-h`
+fn is_value(word: &str) -> bool {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some('-' | '|' | '[') | None => false,
+        Some(c) if c.is_uppercase() => !chars.any(char::is_lowercase),
+        Some(_) => true,
+    }
+}
+
+fn extract_opt(line: &str) -> Option<Opt> {
     const LONG: &[char] = &['-', '-'];
+    let mut words = &line.split_whitespace().collect::<Vec<_>>()[..];
     if words.is_empty() {
         return None;
     }
+    // The option and its value, before the description
+    let head = line.trim_start().split("  ").next().unwrap_or_default();
     let first = &words[0];
-    let short = if first.starts_with('-') && !first.starts_with(LONG) {
+    let short = if first.starts_with('-') && !first.starts_with("--") {
         words = &words[1..];
-        let mut chars = first.chars();
-        chars.next();
-        Some(chars.collect::<String>())
+        let short = first.trim_end_matches(',');
+        (short.len() > 1).then(|| String::from(short))
     } else {
         None
     };
@@ -227,6 +497,15 @@ fn extract_opt(mut words: &[&str]) -> Option<Opt> {
         if opt.starts_with('-') {
             continue;
         }
+        // e.g., `--file=<FILE>` or `--file <FILE>`, but not `--color[=<WHEN>]`
+        let value = if opt.contains("[=") {
+            false
+        } else {
+            opt.contains('=')
+                || rest
+                    .first()
+                    .is_some_and(|w| is_value(w) && head.split_whitespace().any(|h| h == *w))
+        };
         for delim in ['=', '[', ']'] {
             if let Some(idx) = opt.find(delim) {
                 opt = &opt[..idx];
@@ -239,14 +518,24 @@ fn extract_opt(mut words: &[&str]) -> Option<Opt> {
         if long.len() <= 2 {
             continue;
         }
-        return Some(Opt { short, long });
+        return Some(Opt {
+            short,
+            long,
+            arity: usize::from(value),
+        });
     }
     None
 }
 
-fn extract_text(conf: &ConfigFile, text: String) -> (HashMap<String, Flag>, Cmds) {
-    let mut opts = HashSet::new();
+fn extract_text(
+    conf: &ConfigFile,
+    previous: Option<&Cmd>,
+    text: String,
+) -> Result<(HashMap<String, Flag>, Cmds), ExtractError> {
+    let mut opts = HashMap::<String, Opt>::new();
     let mut sub_names = HashSet::<String>::from_iter(conf.extra_subs.iter().cloned());
+    let mut sub_descs = HashMap::<String, String>::new();
+    let mut opt_descs = HashMap::<String, String>::new();
     if conf.extract_subs {
         for mut line in text.lines() {
             if !line.starts_with([' ', ' ']) {
@@ -255,6 +544,9 @@ fn extract_text(conf: &ConfigFile, text: String) -> (HashMap<String, Flag>, Cmds
             line = line.trim_start();
             let words = line.split_whitespace().collect::<Vec<_>>();
             if let Some(long) = extract_sub(words.as_slice()) {
+                if let Some(desc) = extract_desc(line) {
+                    sub_descs.entry(long.clone()).or_insert(desc);
+                }
                 sub_names.insert(long);
             }
         }
@@ -262,9 +554,11 @@ fn extract_text(conf: &ConfigFile, text: String) -> (HashMap<String, Flag>, Cmds
     if conf.extract_flags {
         for mut line in text.lines() {
             line = line.trim_start();
-            let words = line.split_whitespace().collect::<Vec<_>>();
-            if let Some(opt) = extract_opt(words.as_slice()) {
-                opts.insert(opt);
+            if let Some(opt) = extract_opt(line) {
+                if let Some(desc) = extract_desc(line) {
+                    opt_descs.entry(opt.long.clone()).or_insert(desc);
+                }
+                opts.entry(opt.long.clone()).or_insert(opt);
             }
         }
     }
@@ -274,47 +568,69 @@ fn extract_text(conf: &ConfigFile, text: String) -> (HashMap<String, Flag>, Cmds
     }
 
     let sub_name_vec = Vec::from_iter(sub_names.iter().cloned());
-    opts.extend(conf.flags.iter().map(|(long, flag)| Opt {
-        short: Some(flag.short.clone()),
-        long: long.clone(),
-    }));
-    let opt_names = opts.into_iter().map(|o| o.long).collect::<Vec<_>>();
+    for long in conf.flags.keys().map(|l| flag_name(l)) {
+        opts.entry(String::from(long)).or_insert_with(|| Opt {
+            short: None,
+            long: String::from(long),
+            arity: 0,
+        });
+    }
+    let opt_names = opts.keys().cloned().collect::<Vec<_>>();
     let deconflicted = deconflict(
         conf,
+        previous,
         opt_names.as_slice(),
         sub_name_vec.as_slice(),
         conf.deny.as_slice(),
-    );
+    )?;
+    let short_for = |long: &String| {
+        deconflicted.get(long).cloned().ok_or_else(|| {
+            ExtractError::InvalidConfig(format!("couldn't find an abbreviation for `{long}`"))
+        })
+    };
 
     let mut subs = Cmds(BTreeMap::new());
     for long in sub_names {
-        let short = deconflicted.get(&long).unwrap().clone();
+        let short = short_for(&long)?;
         if long == short {
             debug!("Couldn't abbreviate {short}");
         }
         subs.0.insert(
-            long,
+            long.clone(),
             Cmd {
                 short,
+                desc: sub_descs.remove(&long),
                 flags: HashMap::new(),
-                no_args: conf.no_args,
+                no_args: conf.no_args.then_some(true),
                 subs: Cmds::default(),
+                values: conf.subs.get(&long).and_then(|c| c.values.clone()),
+                disable: None,
             },
         );
     }
 
     let mut flags = HashMap::<String, Flag>::new();
     for mut long in opt_names {
-        let short = deconflicted.get(&long).unwrap().clone();
+        let short = short_for(&long)?;
         if long == short {
             debug!("Couldn't abbreviate {short}");
         }
-        if let Some(f) = flags.get(&short) {
-            assert_eq!(f.short, long);
-        }
+        let flag_conf = conf.flag(&long);
+        let opt = opts.remove(&long);
         let flag = Flag {
             short,
-            squish: conf.flags.get(&long).map(|f| f.squish).unwrap_or(false),
+            desc: flag_conf
+                .and_then(|f| f.desc.clone())
+                .or_else(|| opt_descs.remove(&long)),
+            squish: flag_conf.map(|f| f.squish).unwrap_or(false),
+            arity: flag_conf
+                .and_then(|f| f.arity)
+                .or_else(|| opt.as_ref().map(|o| o.arity))
+                .unwrap_or_default(),
+            alias: flag_conf
+                .and_then(|f| f.alias.clone())
+                .or_else(|| opt.and_then(|o| o.short)),
+            values: flag_conf.and_then(|f| f.values.clone()),
         };
         if !long.starts_with(['-', '-']) {
             long = format!("--{long}");
@@ -322,1130 +638,316 @@ fn extract_text(conf: &ConfigFile, text: String) -> (HashMap<String, Flag>, Cmds
         flags.insert(long, flag);
     }
     subs = if conf.stop { Cmds::default() } else { subs };
-    (flags, subs)
+    check_conflicts(&flags, &subs)?;
+    Ok((flags, subs))
 }
 
-fn help(args: &[String]) -> Option<String> {
-    let mut builder = Command::new(&args[0]);
-    builder.args(&args[1..]).arg("--help");
-    debug!("Running {builder:?}");
-    let output = builder.output().unwrap();
-    if !output.status.success() {
-        return None;
+/// Flags and subcommands share a namespace, see [`deconflict`].
+fn check_conflicts(flags: &HashMap<String, Flag>, subs: &Cmds) -> Result<(), ExtractError> {
+    let mut seen = HashMap::<&str, &str>::with_capacity(flags.len() + subs.0.len());
+    let flags = flags
+        .iter()
+        .map(|(long, f)| (long.trim_start_matches('-'), &f.short));
+    let subs = subs.0.iter().map(|(long, s)| (long.as_str(), &s.short));
+    for (long, short) in flags.chain(subs) {
+        if let Some(other) = seen.insert(short, long)
+            && other != long
+        {
+            return Err(ExtractError::Conflict(
+                short.clone(),
+                String::from(other),
+                String::from(long),
+            ));
+        }
     }
-    String::from_utf8(output.stdout).ok()
+    Ok(())
 }
 
-pub(super) fn extract_recursive(
-    mut prefix: Vec<String>,
-    conf: ConfigFile,
-    long: String,
-) -> Option<Cmd> {
-    prefix.push(long.clone());
+/// Extract a single command or subcommand. Its subcommands only have shorts
+/// and descriptions, see [`extract`].
+fn extract_one(
+    helper: &Helper,
+    prefix: &[String],
+    conf: &ConfigFile,
+    previous: Option<&Cmd>,
+) -> Result<Option<Cmd>, ExtractError> {
+    let long = prefix.last().expect("non-empty prefix");
+    let Some(first) = long.chars().next() else {
+        let parent = &prefix[..prefix.len() - 1];
+        return Err(ExtractError::InvalidConfig(if parent.is_empty() {
+            String::from("empty command name")
+        } else {
+            format!("empty subcommand name in `{}`", parent.join(" "))
+        }));
+    };
     let h = if conf.extract_subs || conf.extract_flags {
-        help(&prefix)?
+        let Some(h) = helper.help(prefix)? else {
+            return Ok(None);
+        };
+        h
     } else {
         String::new()
     };
-    let (flags, subs0) = extract_text(&conf, h);
-
-    let mut subs = Cmds(BTreeMap::new());
-    for (long, sub0) in subs0.0 {
-        let sub_conf = conf.subs.get(&long).cloned().unwrap_or_default();
-        if let Some(mut sub) = extract_recursive(prefix.clone(), sub_conf, long.clone()) {
-            sub.short = sub0.short; // already deconflicted
-            subs.0.insert(long, sub);
-        } else {
-            subs.0.insert(long, sub0);
-        }
-    }
-
+    let (flags, subs) = extract_text(conf, previous, h)?;
     if conf.no_args && !subs.is_empty() {
         warn!("`no_args` specified, but {long} has subcommands");
     }
-    Some(Cmd {
-        short: conf
-            .short
-            .unwrap_or_else(|| String::from(long.chars().next().unwrap())),
+    let short = conf
+        .short
+        .clone()
+        .or_else(|| previous.map(|p| p.short.clone()))
+        .unwrap_or_else(|| String::from(first));
+    Ok(Some(Cmd {
+        short,
+        desc: None,
         flags,
-        no_args: conf.no_args,
+        no_args: conf.no_args.then_some(true),
         subs,
-    })
+        values: conf.values.clone(),
+        disable: None,
+    }))
+}
+
+/// A command or subcommand that hasn't been extracted yet
+#[derive(Debug)]
+struct Task<'a> {
+    prefix: Vec<String>,
+    conf: ConfigFile,
+    previous: Option<&'a Cmd>,
 }
 
-pub(super) fn extract(conf: ConfigFile, long: String) -> Option<Cmd> {
-    // conf.extract_flags = true;
-    // conf.extract_subs = true;
-    extract_recursive(Vec::new(), conf, long)
+/// Put the extracted subcommands of `prefix` back together into a tree.
+fn assemble(extracted: &mut HashMap<Vec<String>, Cmd>, prefix: &mut Vec<String>) -> Option<Cmd> {
+    let mut cmd = extracted.remove(prefix.as_slice())?;
+    for (long, sub0) in &mut cmd.subs.0 {
+        prefix.push(long.clone());
+        if let Some(mut sub) = assemble(extracted, prefix) {
+            sub.short = std::mem::take(&mut sub0.short); // already deconflicted
+            sub.desc = sub0.desc.take();
+            *sub0 = sub;
+        }
+        prefix.pop();
+    }
+    Some(cmd)
+}
+
+/// Extract several tools, running up to `jobs` invocations of `--help` at once.
+pub(super) fn extract(
+    helper: &Helper,
+    jobs: usize,
+    tools: Vec<(String, ConfigFile, Option<&Cmd>)>,
+) -> Result<BTreeMap<String, Cmd>, ExtractError> {
+    let extracted = Mutex::new(HashMap::<Vec<String>, Cmd>::new());
+    let tasks = tools
+        .iter()
+        .map(|(long, conf, previous)| Task {
+            prefix: vec![long.clone()],
+            conf: conf.clone(),
+            previous: *previous,
+        })
+        .collect();
+    pool::run(jobs, tasks, |task: Task<'_>| -> Result<_, ExtractError> {
+        let Some(cmd) = extract_one(helper, &task.prefix, &task.conf, task.previous)? else {
+            return Ok(Vec::new());
+        };
+        let subs = cmd
+            .subs
+            .0
+            .keys()
+            .map(|long| {
+                let mut prefix = task.prefix.clone();
+                prefix.push(long.clone());
+                Task {
+                    prefix,
+                    conf: task.conf.subs.get(long).cloned().unwrap_or_default(),
+                    previous: task.previous.and_then(|p| p.subs.0.get(long)),
+                }
+            })
+            .collect();
+        extracted
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(task.prefix, cmd);
+        Ok(subs)
+    })?;
+
+    let mut extracted = extracted
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner);
+    let mut result = BTreeMap::new();
+    for (long, _, _) in tools {
+        if let Some(cmd) = assemble(&mut extracted, &mut vec![long.clone()]) {
+            result.insert(long, cmd);
+        }
+    }
+    Ok(result)
 }
 
-pub(super) fn go(conf: Config) {
-    let conf_file = if let Some(conf) = conf.conf {
-        ConfigFile::from_file(conf)
+/// Report subcommands and flags of `previous` that are missing from `cmd`.
+fn report_removed(long: &str, previous: &Cmd, cmd: &Cmd) {
+    for (flag, fl) in BTreeMap::from_iter(&previous.flags) {
+        if !cmd.flags.contains_key(flag) {
+            warn!("Removed flag: {long} {flag} (was {})", fl.short);
+        }
+    }
+    for (sub_long, prev_sub) in &previous.subs.0 {
+        let sub_path = format!("{long} {sub_long}");
+        match cmd.subs.0.get(sub_long) {
+            Some(sub) => report_removed(&sub_path, prev_sub, sub),
+            None => warn!("Removed subcommand: {sub_path} (was {})", prev_sub.short),
+        }
+    }
+}
+
+fn extract_all(
+    opts: Options,
+    tools: Vec<(String, ConfigFile)>,
+) -> Result<BTreeMap<String, Cmd>, ExtractError> {
+    let previous = if let Some(prev) = opts.previous {
+        super::expand::ConfigFile::from_file(prev)?.cmds
     } else {
-        ConfigFile::default()
+        Cmds::default()
     };
-    if let Some(extracted) = extract(conf_file, conf.cmd.clone()) {
-        if conf.print_subs {
-            let subs = BTreeMap::from_iter(extracted.subs.0);
-            for (long, sub) in subs {
+    let helper = Helper {
+        timeout: Duration::from_secs(opts.timeout),
+        stderr: opts.stderr,
+        record: opts.record,
+        replay: opts.replay,
+    };
+    let jobs = opts
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZero::get));
+    let tools = tools
+        .into_iter()
+        .map(|(long, conf)| {
+            let prev = previous.0.get(&long);
+            (long, conf, prev)
+        })
+        .collect();
+    let extracted = extract(&helper, jobs, tools)?;
+    for (long, cmd) in &extracted {
+        if let Some(prev) = previous.0.get(long) {
+            report_removed(long, prev, cmd);
+        }
+    }
+    Ok(extracted)
+}
+
+pub(super) fn go(conf: Config) -> Result<(), ExtractError> {
+    let tools = if let Some(manifest) = conf.manifest {
+        Manifest::from_file(manifest)?.configs()?
+    } else {
+        let conf_file = if let Some(conf) = conf.conf {
+            ConfigFile::from_file(conf)?
+        } else {
+            ConfigFile::default()
+        };
+        // clap ensures this is present without a manifest
+        vec![(conf.cmd.unwrap_or_default(), conf_file)]
+    };
+    let extracted = extract_all(conf.opts, tools)?;
+    if conf.print_subs {
+        for cmd in extracted.into_values() {
+            for (long, sub) in cmd.subs.0 {
                 println!("{long} --> {}", sub.short);
             }
-        } else {
-            let gen_conf = super::expand::ConfigFile {
-                cmds: Cmds(BTreeMap::from([(conf.cmd, extracted)])),
-            };
-            println!("{}", toml::to_string(&gen_conf).unwrap());
         }
+    } else if !extracted.is_empty() {
+        let gen_conf = super::expand::ConfigFile {
+            cmds: Cmds(extracted),
+            ..Default::default()
+        };
+        println!("{}", toml::to_string(&gen_conf)?);
     }
+    Ok(())
+}
+
+/// Top-level shorts must be distinct from each other, and from the names of
+/// the other tools.
+fn check_top_level(cmds: &Cmds) -> Result<(), ExtractError> {
+    let mut seen = HashMap::<&str, &str>::with_capacity(cmds.0.len());
+    for (long, cmd) in &cmds.0 {
+        if let Some(other) = seen.insert(&cmd.short, long) {
+            return Err(ExtractError::Conflict(
+                cmd.short.clone(),
+                String::from(other),
+                long.clone(),
+            ));
+        }
+    }
+    for (long, cmd) in &cmds.0 {
+        if cmd.short != *long && cmds.0.contains_key(&cmd.short) {
+            return Err(ExtractError::Conflict(
+                cmd.short.clone(),
+                long.clone(),
+                cmd.short.clone(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// No abbreviation may expand to two commands, e.g., `gho` to both `gh org`
+/// and `git hook`, see [`compile_with_conflicts`]
+fn check_compiled(cmds: &Cmds) -> Result<(), ExtractError> {
+    let (_compiled, conflicts) = compile_with_conflicts(cmds);
+    match conflicts.into_iter().next() {
+        Some(Conflict { key, first, second }) => Err(ExtractError::Conflict(
+            key,
+            String::from(first.trim_end()),
+            String::from(second.trim_end()),
+        )),
+        None => Ok(()),
+    }
+}
+
+pub(super) fn build(conf: Build) -> Result<(), ExtractError> {
+    let manifest = Manifest::from_file(&conf.manifest)?;
+    let extracted = extract_all(conf.opts, manifest.configs()?)?;
+    let gen_conf = super::expand::ConfigFile {
+        cmds: Cmds(extracted),
+        ..Default::default()
+    };
+    check_top_level(&gen_conf.cmds)?;
+    check_compiled(&gen_conf.cmds)?;
+    let out = format!(
+        "# generated by zbr build {}\n{}",
+        conf.manifest.display(),
+        toml::to_string(&gen_conf)?
+    );
+    if let Some(output) = manifest.output {
+        std::fs::write(&output, out).map_err(|e| ExtractError::Io(e, output))?;
+    } else {
+        print!("{out}");
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
     use std::path::PathBuf;
 
     use expect_test::expect;
 
-    use super::{ConfigFile, deconflict, extract_text};
-
-    const CABAL_HELP: &str = r#"
-Command line interface to the Haskell Cabal infrastructure.
-
-See http://www.haskell.org/cabal/ for more information.
-
-Usage: cabal [GLOBAL FLAGS] [COMMAND [FLAGS]]
-
-Commands:
- [global]
-  user-config            Display and update the user's global cabal configuration.
-  help                   Help about commands.
-
- [package database]
-  update                 Updates list of known packages.
-  list                   List packages matching a search string.
-  info                   Display detailed information about a particular package.
-
- [initialization and download]
-  init                   Create a new cabal package.
-  fetch                  Downloads packages for later installation.
-  get                    Download/Extract a package's source code (repository).
-
- [project configuration]
-  configure              Add extra project configuration.
-  freeze                 Freeze dependencies.
-  gen-bounds             Generate dependency bounds.
-  outdated               Check for outdated dependencies.
-
- [project building and installing]
-  build                  Compile targets within the project.
-  install                Install packages.
-  haddock                Build Haddock documentation.
-  haddock-project        Generate Haddocks HTML documentation for the cabal project.
-  clean                  Clean the package store and remove temporary files.
-
- [running and testing]
-  list-bin               List the path to a single executable.
-  repl                   Open an interactive session for the given component.
-  run                    Run an executable.
-  bench                  Run benchmarks.
-  test                   Run test-suites.
-  exec                   Give a command access to the store.
-
- [sanity checks and shipping]
-  check                  Check the package for common mistakes.
-  sdist                  Generate a source distribution file (.tar.gz).
-  upload                 Uploads source packages or documentation to Hackage.
-  report                 Upload build reports to a remote server.
-
- [deprecated]
-  unpack                 Deprecated alias for 'get'.
-  hscolour               Generate HsColour colourised code, in HTML format.
-
- [new-style projects (forwards-compatible aliases)]
-  v2-build               Compile targets within the project.
-  v2-configure           Add extra project configuration.
-  v2-repl                Open an interactive session for the given component.
-  v2-run                 Run an executable.
-  v2-test                Run test-suites.
-  v2-bench               Run benchmarks.
-  v2-freeze              Freeze dependencies.
-  v2-haddock             Build Haddock documentation.
-  v2-exec                Give a command access to the store.
-  v2-update              Updates list of known packages.
-  v2-install             Install packages.
-  v2-clean               Clean the package store and remove temporary files.
-  v2-sdist               Generate a source distribution file (.tar.gz).
-
- [legacy command aliases]
-  v1-build               Compile all/specific components.
-  v1-configure           Prepare to build the package.
-  v1-repl                Open an interpreter session for the given component.
-  v1-run                 Builds and runs an executable.
-  v1-test                Run all/specific tests in the test suite.
-  v1-bench               Run all/specific benchmarks.
-  v1-freeze              Freeze dependencies.
-  v1-haddock             Generate Haddock HTML documentation.
-  v1-install             Install packages.
-  v1-clean               Clean up after a build.
-  v1-copy                Copy the files of all/specific components to install locations.
-  v1-register            Register this package with the compiler.
-  v1-reconfigure         Reconfigure the package if necessary.
-
- [other]
-  haddock-project        Generate Haddocks HTML documentation for the cabal project.
-  new-haddock-project    Generate Haddocks HTML documentation for the cabal project.
-  v2-haddock-project     Generate Haddocks HTML documentation for the cabal project.
-
-For more information about a command use:
-   cabal COMMAND --help
-or cabal help COMMAND
-
-To install Cabal packages from hackage use:
-  cabal install foo [--dry-run]
-
-Occasionally you need to update the list of available packages:
-  cabal update
-
-Global flags:
- -h, --help                     Show this help text
- -V, --version                  Print version information
- --numeric-version              Print just the version number
- --config-file=FILE             Set an alternate location for the config file
- --ignore-expiry                Ignore expiry dates on signed metadata (use
-                                only in exceptional circumstances)
- --http-transport=HttpTransport
-                                Set a transport for http(s) requests. Accepts
-                                'curl', 'wget', 'powershell', and
-                                'plain-http'. (default: 'curl')
- --nix[=(True or False)]        Nix integration: run commands through
-                                nix-shell if a 'shell.nix' file exists
-                                (default is False)
- --enable-nix                   Enable Nix integration: run commands through
-                                nix-shell if a 'shell.nix' file exists
- --disable-nix                  Disable Nix integration
- --store-dir=DIR                The location of the build store
- --active-repositories=REPOS    The active package repositories (set to
-                                ':none' to disable all repositories)"#;
-
-    const CARGO_HELP: &str = r#"
-Rust's package manager
-
-Usage: cargo [+toolchain] [OPTIONS] [COMMAND]
-
-Options:
-  -V, --version             Print version info and exit
-      --list                List installed commands
-      --explain <CODE>      Run `rustc --explain CODE`
-  -v, --verbose...          Use verbose output (-vv very verbose/build.rs output)
-  -q, --quiet               Do not print cargo log messages
-      --color <WHEN>        Coloring: auto, always, never
-  -C <DIRECTORY>            Change to DIRECTORY before doing anything (nightly-only)
-      --frozen              Require Cargo.lock and cache are up to date
-      --locked              Require Cargo.lock is up to date
-      --offline             Run without accessing the network
-      --config <KEY=VALUE>  Override a configuration value
-  -Z <FLAG>                 Unstable (nightly-only) flags to Cargo, see 'cargo -Z help' for details
-  -h, --help                Print help
-
-Some common cargo commands are (see all commands with --list):
-    build, b    Compile the current package
-    check, c    Analyze the current package and report errors, but don't build object files
-    clean       Remove the target directory
-    doc, d      Build this package's and its dependencies' documentation
-    new         Create a new cargo package
-    init        Create a new cargo package in an existing directory
-    add         Add dependencies to a manifest file
-    remove      Remove dependencies from a manifest file
-    run, r      Run a binary or example of the local package
-    test, t     Run the tests
-    bench       Run the benchmarks
-    update      Update dependencies listed in Cargo.lock
-    search      Search registry for crates
-    publish     Package and upload this package to the registry
-    install     Install a Rust binary. Default location is $HOME/.cargo/bin
-    uninstall   Uninstall a Rust binary
-
-See 'cargo help <command>' for more information on a specific command."#;
-
-    const DOCKER_HELP: &str = r#"
-Usage:  docker [OPTIONS] COMMAND
-
-A self-sufficient runtime for containers
-
-Options:
-      --config string      Location of client config files (default "/home/langston/.docker")
-  -c, --context string     Name of the context to use to connect to the daemon (overrides
-                           DOCKER_HOST env var and default context set with "docker context use")
-  -D, --debug              Enable debug mode
-  -H, --host list          Daemon socket(s) to connect to
-  -l, --log-level string   Set the logging level ("debug"|"info"|"warn"|"error"|"fatal") (default
-                           "info")
-      --tls                Use TLS; implied by --tlsverify
-      --tlscacert string   Trust certs signed only by this CA (default
-                           "/home/langston/.docker/ca.pem")
-      --tlscert string     Path to TLS certificate file (default "/home/langston/.docker/cert.pem")
-      --tlskey string      Path to TLS key file (default "/home/langston/.docker/key.pem")
-      --tlsverify          Use TLS and verify the remote
-  -v, --version            Print version information and quit
-
-Management Commands:
-  builder     Manage builds
-  buildx*     Docker Buildx (Docker Inc., 0.0.0+unknown)
-  compose*    Docker Compose (Docker Inc., 2.5.1)
-  config      Manage Docker configs
-  container   Manage containers
-  context     Manage contexts
-  image       Manage images
-  manifest    Manage Docker image manifests and manifest lists
-  network     Manage networks
-  node        Manage Swarm nodes
-  plugin      Manage plugins
-  secret      Manage Docker secrets
-  service     Manage services
-  stack       Manage Docker stacks
-  swarm       Manage Swarm
-  system      Manage Docker
-  trust       Manage trust on Docker images
-  volume      Manage volumes
-
-Commands:
-  attach      Attach local standard input, output, and error streams to a running container
-  build       Build an image from a Dockerfile
-  commit      Create a new image from a container's changes
-  cp          Copy files/folders between a container and the local filesystem
-  create      Create a new container
-  diff        Inspect changes to files or directories on a container's filesystem
-  events      Get real time events from the server
-  exec        Run a command in a running container
-  export      Export a container's filesystem as a tar archive
-  history     Show the history of an image
-  images      List images
-  import      Import the contents from a tarball to create a filesystem image
-  info        Display system-wide information
-  inspect     Return low-level information on Docker objects
-  kill        Kill one or more running containers
-  load        Load an image from a tar archive or STDIN
-  login       Log in to a Docker registry
-  logout      Log out from a Docker registry
-  logs        Fetch the logs of a container
-  pause       Pause all processes within one or more containers
-  port        List port mappings or a specific mapping for the container
-  ps          List containers
-  pull        Pull an image or a repository from a registry
-  push        Push an image or a repository to a registry
-  rename      Rename a container
-  restart     Restart one or more containers
-  rm          Remove one or more containers
-  rmi         Remove one or more images
-  run         Run a command in a new container
-  save        Save one or more images to a tar archive (streamed to STDOUT by default)
-  search      Search the Docker Hub for images
-  start       Start one or more stopped containers
-  stats       Display a live stream of container(s) resource usage statistics
-  stop        Stop one or more running containers
-  tag         Create a tag TARGET_IMAGE that refers to SOURCE_IMAGE
-  top         Display the running processes of a container
-  unpause     Unpause all processes within one or more containers
-  update      Update configuration of one or more containers
-  version     Show the Docker version information
-  wait        Block until one or more containers stop, then print their exit codes
-
-Run 'docker COMMAND --help' for more information on a command."#;
-
-    const ELAN_HELP: &str = "
-elan 4.1.1
-The Lean toolchain installer
-
-USAGE:
-    elan [FLAGS] <SUBCOMMAND>
-
-FLAGS:
-    -v, --verbose    Enable verbose output
-    -h, --help       Prints help information
-    -V, --version    Prints version information
-
-SUBCOMMANDS:
-    show           Show the active and installed toolchains
-    default        Set the default toolchain
-    toolchain      Modify or query the installed toolchains
-    override       Modify directory toolchain overrides
-    run            Run a command with an environment configured for a given toolchain
-    which          Display which binary will be run for a given command
-    self           Modify the elan installation
-    completions    Generate completion scripts for your shell
-    help           Prints this message or the help of the given subcommand(s)
-
-DISCUSSION:
-    elan manages your installations of the Lean theorem prover.
-    It places `lean` and `lake` binaries in your `PATH` that automatically
-    select and, if necessary, download the Lean version described in your
-    project's `lean-toolchain` file. You can also install, select, run,
-    and uninstall Lean versions manually using the commands of the `elan`
-    executable.";
-
-    const GIT_HELP: &str = "
-usage: git [-v | --version] [-h | --help] [-C <path>] [-c <name>=<value>]
-           [--exec-path[=<path>]] [--html-path] [--man-path] [--info-path]
-           [-p | --paginate | -P | --no-pager] [--no-replace-objects] [--bare]
-           [--git-dir=<path>] [--work-tree=<path>] [--namespace=<name>]
-           [--super-prefix=<path>] [--config-env=<name>=<envvar>]
-           <command> [<args>]
-
-These are common Git commands used in various situations:
-
-start a working area (see also: git help tutorial)
-   clone     Clone a repository into a new directory
-   init      Create an empty Git repository or reinitialize an existing one
-
-work on the current change (see also: git help everyday)
-   add       Add file contents to the index
-   mv        Move or rename a file, a directory, or a symlink
-   restore   Restore working tree files
-   rm        Remove files from the working tree and from the index
-
-examine the history and state (see also: git help revisions)
-   bisect    Use binary search to find the commit that introduced a bug
-   diff      Show changes between commits, commit and working tree, etc
-   grep      Print lines matching a pattern
-   log       Show commit logs
-   show      Show various types of objects
-   status    Show the working tree status
-
-grow, mark and tweak your common history
-   branch    List, create, or delete branches
-   commit    Record changes to the repository
-   merge     Join two or more development histories together
-   rebase    Reapply commits on top of another base tip
-   reset     Reset current HEAD to the specified state
-   switch    Switch branches
-   tag       Create, list, delete or verify a tag object signed with GPG
-
-collaborate (see also: git help workflows)
-   fetch     Download objects and refs from another repository
-   pull      Fetch from and integrate with another repository or a local branch
-   push      Update remote refs along with associated objects
-
-'git help -a' and 'git help -g' list available subcommands and some
-concept guides. See 'git help <command>' or 'git help <concept>'
-to read about a specific subcommand or concept.
-See 'git help git' for an overview of the system.";
-
-    const GIT_SUBMODULE_HELP: &str = r#"GIT-SUBMODULE(1)                              Git Manual                             GIT-SUBMODULE(1)
-
-NNAAMMEE
-       git-submodule - Initialize, update or inspect submodules
-
-SSYYNNOOPPSSIISS
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] [--cached]
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] add [<options>] [--] <repository> [<path>]
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] status [--cached] [--recursive] [--] [<path>...]
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] init [--] [<path>...]
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] deinit [-f|--force] (--all|[--] <path>...)
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] update [<options>] [--] [<path>...]
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] set-branch [<options>] [--] <path>
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] set-url [--] <path> <newurl>
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] summary [<options>] [--] [<path>...]
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] foreach [--recursive] <command>
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] sync [--recursive] [--] [<path>...]
-       _g_i_t _s_u_b_m_o_d_u_l_e [--quiet] absorbgitdirs [--] [<path>...]
-
-DDEESSCCRRIIPPTTIIOONN
-       Inspects, updates and manages submodules.
-
-       For more information about submodules, see ggiittssuubbmmoodduulleess(7).
-
-CCOOMMMMAANNDDSS
-       With no arguments, shows the status of existing submodules. Several subcommands are available
-       to perform operations on the submodules.
-
-       add [-b <branch>] [-f|--force] [--name <name>] [--reference <repository>] [--depth <depth>]
-       [--] <repository> [<path>]
-           Add the given repository as a submodule at the given path to the changeset to be committed
-           next to the current project: the current project is termed the "superproject".
-
-           <repository> is the URL of the new submodule’s origin repository. This may be either an
-           absolute URL, or (if it begins with ./ or ../), the location relative to the
-           superproject’s default remote repository (Please note that to specify a repository _f_o_o_._g_i_t
-           which is located right next to a superproject _b_a_r_._g_i_t, you’ll have to use ....//ffoooo..ggiitt
-           instead of ..//ffoooo..ggiitt - as one might expect when following the rules for relative URLs -
-           because the evaluation of relative URLs in Git is identical to that of relative
-           directories).
-
-           The default remote is the remote of the remote-tracking branch of the current branch. If
-           no such remote-tracking branch exists or the HEAD is detached, "origin" is assumed to be
-           the default remote. If the superproject doesn’t have a default remote configured the
-           superproject is its own authoritative upstream and the current working directory is used
-           instead.
-
-           The optional argument <path> is the relative location for the cloned submodule to exist in
-           the superproject. If <path> is not given, the canonical part of the source repository is
-           used ("repo" for "/path/to/repo.git" and "foo" for "host.xz:foo/.git"). If <path> exists
-           and is already a valid Git repository, then it is staged for commit without cloning. The
-           <path> is also used as the submodule’s logical name in its configuration entries unless
-           ----nnaammee is used to specify a logical name.
-
-           The given URL is recorded into ..ggiittmmoodduulleess for use by subsequent users cloning the
-           superproject. If the URL is given relative to the superproject’s repository, the
-           presumption is the superproject and submodule repositories will be kept together in the
-           same relative location, and only the superproject’s URL needs to be provided.
-           git-submodule will correctly locate the submodule using the relative URL in ..ggiittmmoodduulleess.
-
-       status [--cached] [--recursive] [--] [<path>...]
-           Show the status of the submodules. This will print the SHA-1 of the currently checked out
-           commit for each submodule, along with the submodule path and the output of _g_i_t _d_e_s_c_r_i_b_e
-           for the SHA-1. Each SHA-1 will possibly be prefixed with -- if the submodule is not
-           initialized, ++ if the currently checked out submodule commit does not match the SHA-1
-           found in the index of the containing repository and UU if the submodule has merge
-           conflicts.
-
-           If ----ccaacchheedd is specified, this command will instead print the SHA-1 recorded in the
-           superproject for each submodule.
-
-           If ----rreeccuurrssiivvee is specified, this command will recurse into nested submodules, and show
-           their status as well.
-
-           If you are only interested in changes of the currently initialized submodules with respect
-           to the commit recorded in the index or the HEAD, ggiitt--ssttaattuuss(1) and ggiitt--ddiiffff(1) will
-           provide that information too (and can also report changes to a submodule’s work tree).
-
-       init [--] [<path>...]
-           Initialize the submodules recorded in the index (which were added and committed elsewhere)
-           by setting ssuubbmmoodduullee..$$nnaammee..uurrll in .git/config. It uses the same setting from ..ggiittmmoodduulleess
-           as a template. If the URL is relative, it will be resolved using the default remote. If
-           there is no default remote, the current repository will be assumed to be upstream.
-
-           Optional <path> arguments limit which submodules will be initialized. If no path is
-           specified and submodule.active has been configured, submodules configured to be active
-           will be initialized, otherwise all submodules are initialized.
-
-           When present, it will also copy the value of ssuubbmmoodduullee..$$nnaammee..uuppddaattee. This command does not
-           alter existing information in .git/config. You can then customize the submodule clone URLs
-           in .git/config for your local setup and proceed to ggiitt ssuubbmmoodduullee uuppddaattee; you can also just
-           use ggiitt ssuubbmmoodduullee uuppddaattee ----iinniitt without the explicit _i_n_i_t step if you do not intend to
-           customize any submodule locations.
-
-           See the add subcommand for the definition of default remote.
-
-       deinit [-f|--force] (--all|[--] <path>...)
-           Unregister the given submodules, i.e. remove the whole ssuubbmmoodduullee..$$nnaammee section from
-           .git/config together with their work tree. Further calls to ggiitt ssuubbmmoodduullee uuppddaattee, ggiitt
-           ssuubbmmoodduullee ffoorreeaacchh and ggiitt ssuubbmmoodduullee ssyynncc will skip any unregistered submodules until they
-           are initialized again, so use this command if you don’t want to have a local checkout of
-           the submodule in your working tree anymore.
-
-           When the command is run without pathspec, it errors out, instead of deinit-ing everything,
-           to prevent mistakes.
-
-           If ----ffoorrccee is specified, the submodule’s working tree will be removed even if it contains
-           local modifications.
-
-           If you really want to remove a submodule from the repository and commit that use ggiitt--rrmm(1)
-           instead. See ggiittssuubbmmoodduulleess(7) for removal options.
-
-       update [--init] [--remote] [-N|--no-fetch] [--[no-]recommend-shallow] [-f|--force]
-       [--checkout|--rebase|--merge] [--reference <repository>] [--depth <depth>] [--recursive]
-       [--jobs <n>] [--[no-]single-branch] [--filter <filter spec>] [--] [<path>...]
-           Update the registered submodules to match what the superproject expects by cloning missing
-           submodules, fetching missing commits in submodules and updating the working tree of the
-           submodules. The "updating" can be done in several ways depending on command line options
-           and the value of ssuubbmmoodduullee..<<nnaammee>>..uuppddaattee configuration variable. The command line option
-           takes precedence over the configuration variable. If neither is given, a _c_h_e_c_k_o_u_t is
-           performed. The _u_p_d_a_t_e procedures supported both from the command line as well as through
-           the ssuubbmmoodduullee..<<nnaammee>>..uuppddaattee configuration are:
-
-           checkout
-               the commit recorded in the superproject will be checked out in the submodule on a
-               detached HEAD.
-
-               If ----ffoorrccee is specified, the submodule will be checked out (using ggiitt cchheecckkoouutt
-               ----ffoorrccee), even if the commit specified in the index of the containing repository
-               already matches the commit checked out in the submodule.
-
-           rebase
-               the current branch of the submodule will be rebased onto the commit recorded in the
-               superproject.
-
-           merge
-               the commit recorded in the superproject will be merged into the current branch in the
-               submodule.
-
-           The following _u_p_d_a_t_e procedures are only available via the ssuubbmmoodduullee..<<nnaammee>>..uuppddaattee
-           configuration variable:
-
-           custom command
-               arbitrary shell command that takes a single argument (the sha1 of the commit recorded
-               in the superproject) is executed. When ssuubbmmoodduullee..<<nnaammee>>..uuppddaattee is set to _!_c_o_m_m_a_n_d, the
-               remainder after the exclamation mark is the custom command.
-
-           none
-               the submodule is not updated.
-
-           If the submodule is not yet initialized, and you just want to use the setting as stored in
-           ..ggiittmmoodduulleess, you can automatically initialize the submodule with the ----iinniitt option.
-
-           If ----rreeccuurrssiivvee is specified, this command will recurse into the registered submodules, and
-           update any nested submodules within.
-
-           If ----ffiilltteerr <<ffiilltteerr ssppeecc>> is specified, the given partial clone filter will be applied to
-           the submodule. See ggiitt--rreevv--lliisstt(1) for details on filter specifications.
-
-       set-branch (-b|--branch) <branch> [--] <path>, set-branch (-d|--default) [--] <path>
-           Sets the default remote tracking branch for the submodule. The ----bbrraanncchh option allows the
-           remote branch to be specified. The ----ddeeffaauulltt option removes the submodule.<name>.branch
-           configuration key, which causes the tracking branch to default to the remote _H_E_A_D.
-
-       set-url [--] <path> <newurl>
-           Sets the URL of the specified submodule to <newurl>. Then, it will automatically
-           synchronize the submodule’s new remote URL configuration.
-
-       summary [--cached|--files] [(-n|--summary-limit) <n>] [commit] [--] [<path>...]
-           Show commit summary between the given commit (defaults to HEAD) and working tree/index.
-           For a submodule in question, a series of commits in the submodule between the given super
-           project commit and the index or working tree (switched by ----ccaacchheedd) are shown. If the
-           option ----ffiilleess is given, show the series of commits in the submodule between the index of
-           the super project and the working tree of the submodule (this option doesn’t allow to use
-           the ----ccaacchheedd option or to provide an explicit commit).
-
-           Using the ----ssuubbmmoodduullee==lloogg option with ggiitt--ddiiffff(1) will provide that information too.
-
-       foreach [--recursive] <command>
-           Evaluates an arbitrary shell command in each checked out submodule. The command has access
-           to the variables $name, $sm_path, $displaypath, $sha1 and $toplevel: $name is the name of
-           the relevant submodule section in ..ggiittmmoodduulleess, $sm_path is the path of the submodule as
-           recorded in the immediate superproject, $displaypath contains the relative path from the
-           current working directory to the submodules root directory, $sha1 is the commit as
-           recorded in the immediate superproject, and $toplevel is the absolute path to the
-           top-level of the immediate superproject. Note that to avoid conflicts with _$_P_A_T_H on
-           Windows, the _$_p_a_t_h variable is now a deprecated synonym of _$_s_m___p_a_t_h variable. Any
-           submodules defined in the superproject but not checked out are ignored by this command.
-           Unless given ----qquuiieett, foreach prints the name of each submodule before evaluating the
-           command. If ----rreeccuurrssiivvee is given, submodules are traversed recursively (i.e. the given
-           shell command is evaluated in nested submodules as well). A non-zero return from the
-           command in any submodule causes the processing to terminate. This can be overridden by
-           adding _|_| _: to the end of the command.
-
-           As an example, the command below will show the path and currently checked out commit for
-           each submodule:
-
-               git submodule foreach 'echo $sm_path `git rev-parse HEAD`'
-
-       sync [--recursive] [--] [<path>...]
-           Synchronizes submodules' remote URL configuration setting to the value specified in
-           ..ggiittmmoodduulleess. It will only affect those submodules which already have a URL entry in
-           .git/config (that is the case when they are initialized or freshly added). This is useful
-           when submodule URLs change upstream and you need to update your local repositories
-           accordingly.
-
-           ggiitt ssuubbmmoodduullee ssyynncc synchronizes all submodules while ggiitt ssuubbmmoodduullee ssyynncc ---- AA synchronizes
-           submodule "A" only.
-
-           If ----rreeccuurrssiivvee is specified, this command will recurse into the registered submodules, and
-           sync any nested submodules within.
-
-       absorbgitdirs
-           If a git directory of a submodule is inside the submodule, move the git directory of the
-           submodule into its superproject’s $$GGIITT__DDIIRR//mmoodduulleess path and then connect the git directory
-           and its working directory by setting the ccoorree..wwoorrkkttrreeee and adding a .git file pointing to
-           the git directory embedded in the superprojects git directory.
-
-           A repository that was cloned independently and later added as a submodule or old setups
-           have the submodules git directory inside the submodule instead of embedded into the
-           superprojects git directory.
-
-           This command is recursive by default.
-
-OOPPTTIIOONNSS
-       -q, --quiet
-           Only print error messages.
-
-       --progress
-           This option is only valid for add and update commands. Progress status is reported on the
-           standard error stream by default when it is attached to a terminal, unless -q is
-           specified. This flag forces progress status even if the standard error stream is not
-           directed to a terminal.
-
-       --all
-           This option is only valid for the deinit command. Unregister all submodules in the working
-           tree.
-
-       -b <branch>, --branch <branch>
-           Branch of repository to add as submodule. The name of the branch is recorded as
-           ssuubbmmoodduullee..<<nnaammee>>..bbrraanncchh in ..ggiittmmoodduulleess for uuppddaattee ----rreemmoottee. A special value of ..  is used
-           to indicate that the name of the branch in the submodule should be the same name as the
-           current branch in the current repository. If the option is not specified, it defaults to
-           the remote _H_E_A_D.
-
-       -f, --force
-           This option is only valid for add, deinit and update commands. When running add, allow
-           adding an otherwise ignored submodule path. When running deinit the submodule working
-           trees will be removed even if they contain local changes. When running update (only
-           effective with the checkout procedure), throw away local changes in submodules when
-           switching to a different commit; and always run a checkout operation in the submodule,
-           even if the commit listed in the index of the containing repository matches the commit
-           checked out in the submodule.
-
-       --cached
-           This option is only valid for status and summary commands. These commands typically use
-           the commit found in the submodule HEAD, but with this option, the commit stored in the
-           index is used instead.
-
-       --files
-           This option is only valid for the summary command. This command compares the commit in the
-           index with that in the submodule HEAD when this option is used.
-
-       -n, --summary-limit
-           This option is only valid for the summary command. Limit the summary size (number of
-           commits shown in total). Giving 0 will disable the summary; a negative number means
-           unlimited (the default). This limit only applies to modified submodules. The size is
-           always limited to 1 for added/deleted/typechanged submodules.
-
-       --remote
-           This option is only valid for the update command. Instead of using the superproject’s
-           recorded SHA-1 to update the submodule, use the status of the submodule’s remote-tracking
-           branch. The remote used is branch’s remote (bbrraanncchh..<<nnaammee>>..rreemmoottee), defaulting to oorriiggiinn.
-           The remote branch used defaults to the remote HHEEAADD, but the branch name may be overridden
-           by setting the ssuubbmmoodduullee..<<nnaammee>>..bbrraanncchh option in either ..ggiittmmoodduulleess or ..ggiitt//ccoonnffiigg (with
-           ..ggiitt//ccoonnffiigg taking precedence).
-
-           This works for any of the supported update procedures (----cchheecckkoouutt, ----rreebbaassee, etc.). The
-           only change is the source of the target SHA-1. For example, ssuubbmmoodduullee uuppddaattee ----rreemmoottee
-           ----mmeerrggee will merge upstream submodule changes into the submodules, while ssuubbmmoodduullee uuppddaattee
-           ----mmeerrggee will merge superproject gitlink changes into the submodules.
-
-           In order to ensure a current tracking branch state, uuppddaattee ----rreemmoottee fetches the
-           submodule’s remote repository before calculating the SHA-1. If you don’t want to fetch,
-           you should use ssuubbmmoodduullee uuppddaattee ----rreemmoottee ----nnoo--ffeettcchh.
-
-           Use this option to integrate changes from the upstream subproject with your submodule’s
-           current HEAD. Alternatively, you can run ggiitt ppuullll from the submodule, which is equivalent
-           except for the remote branch name: uuppddaattee ----rreemmoottee uses the default upstream repository
-           and ssuubbmmoodduullee..<<nnaammee>>..bbrraanncchh, while ggiitt ppuullll uses the submodule’s bbrraanncchh..<<nnaammee>>..mmeerrggee.
-           Prefer ssuubbmmoodduullee..<<nnaammee>>..bbrraanncchh if you want to distribute the default upstream branch with
-           the superproject and bbrraanncchh..<<nnaammee>>..mmeerrggee if you want a more native feel while working in
-           the submodule itself.
-
-       -N, --no-fetch
-           This option is only valid for the update command. Don’t fetch new objects from the remote
-           site.
-
-       --checkout
-           This option is only valid for the update command. Checkout the commit recorded in the
-           superproject on a detached HEAD in the submodule. This is the default behavior, the main
-           use of this option is to override ssuubbmmoodduullee..$$nnaammee..uuppddaattee when set to a value other than
-           cchheecckkoouutt. If the key ssuubbmmoodduullee..$$nnaammee..uuppddaattee is either not explicitly set or set to
-           cchheecckkoouutt, this option is implicit.
-
-       --merge
-           This option is only valid for the update command. Merge the commit recorded in the
-           superproject into the current branch of the submodule. If this option is given, the
-           submodule’s HEAD will not be detached. If a merge failure prevents this process, you will
-           have to resolve the resulting conflicts within the submodule with the usual conflict
-           resolution tools. If the key ssuubbmmoodduullee..$$nnaammee..uuppddaattee is set to mmeerrggee, this option is
-           implicit.
-
-       --rebase
-           This option is only valid for the update command. Rebase the current branch onto the
-           commit recorded in the superproject. If this option is given, the submodule’s HEAD will
-           not be detached. If a merge failure prevents this process, you will have to resolve these
-           failures with ggiitt--rreebbaassee(1). If the key ssuubbmmoodduullee..$$nnaammee..uuppddaattee is set to rreebbaassee, this
-           option is implicit.
-
-       --init
-           This option is only valid for the update command. Initialize all submodules for which "git
-           submodule init" has not been called so far before updating.
-
-       --name
-           This option is only valid for the add command. It sets the submodule’s name to the given
-           string instead of defaulting to its path. The name must be valid as a directory name and
-           may not end with a _/.
-
-       --reference <repository>
-           This option is only valid for add and update commands. These commands sometimes need to
-           clone a remote repository. In this case, this option will be passed to the ggiitt--cclloonnee(1)
-           command.
-
-           NNOOTTEE: Do nnoott use this option unless you have read the note for ggiitt--cclloonnee(1)'s ----rreeffeerreennccee,
-           ----sshhaarreedd, and ----ddiissssoocciiaattee options carefully.
-
-       --dissociate
-           This option is only valid for add and update commands. These commands sometimes need to
-           clone a remote repository. In this case, this option will be passed to the ggiitt--cclloonnee(1)
-           command.
-
-           NNOOTTEE: see the NOTE for the ----rreeffeerreennccee option.
-
-       --recursive
-           This option is only valid for foreach, update, status and sync commands. Traverse
-           submodules recursively. The operation is performed not only in the submodules of the
-           current repo, but also in any nested submodules inside those submodules (and so on).
-
-       --depth
-           This option is valid for add and update commands. Create a _s_h_a_l_l_o_w clone with a history
-           truncated to the specified number of revisions. See ggiitt--cclloonnee(1)
-
-       --[no-]recommend-shallow
-           This option is only valid for the update command. The initial clone of a submodule will
-           use the recommended ssuubbmmoodduullee..<<nnaammee>>..sshhaallllooww as provided by the ..ggiittmmoodduulleess file by
-           default. To ignore the suggestions use ----nnoo--rreeccoommmmeenndd--sshhaallllooww.
-
-       -j <n>, --jobs <n>
-           This option is only valid for the update command. Clone new submodules in parallel with as
-           many jobs. Defaults to the ssuubbmmoodduullee..ffeettcchhJJoobbss option.
-
-       --[no-]single-branch
-           This option is only valid for the update command. Clone only one branch during update:
-           HEAD or one specified by --branch.
-
-       <path>...
-           Paths to submodule(s). When specified this will restrict the command to only operate on
-           the submodules found at the specified paths. (This argument is required with add).
-
-FFIILLEESS
-       When initializing submodules, a ..ggiittmmoodduulleess file in the top-level directory of the containing
-       repository is used to find the url of each submodule. This file should be formatted in the
-       same way as $$GGIITT__DDIIRR//ccoonnffiigg. The key to each submodule url is "submodule.$name.url". See
-       ggiittmmoodduulleess(5) for details.
-
-SSEEEE AALLSSOO
-       ggiittssuubbmmoodduulleess(7), ggiittmmoodduulleess(5).
-
-GGIITT
-       Part of the ggiitt(1) suite
-
-Git 2.37.2                                    08/11/2022                             GIT-SUBMODULE(1)"#;
-
-    const GLAB_HELP: &str = "
-GLab is an open source GitLab CLI tool bringing GitLab to your command line
-
-USAGE
-  glab <command> <subcommand> [flags]
-
-CORE COMMANDS
-  alias:       Create, list and delete aliases
-  api:         Make an authenticated request to GitLab API
-  auth:        Manage glab's authentication state
-  check-update: Check for latest glab releases
-  ci:          Work with GitLab CI pipelines and jobs
-  completion:  Generate shell completion scripts
-  config:      Set and get glab settings
-  help:        Help about any command
-  issue:       Work with GitLab issues
-  label:       Manage labels on remote
-  mr:          Create, view and manage merge requests
-  release:     Manage GitLab releases
-  repo:        Work with GitLab repositories and projects
-  ssh-key:     Manage SSH keys
-  user:        Interact with user
-  variable:    Manage GitLab Project and Group Variables
-  version:     show glab version information
-
-FLAGS
-      --help      Show help for command
-  -v, --version   show glab version information
-
-ENVIRONMENT VARIABLES
-  GITLAB_TOKEN: an authentication token for API requests. Setting this avoids being
-  prompted to authenticate and overrides any previously stored credentials.
-  Can be set in the config with 'glab config set token xxxxxx'
-  
-  GITLAB_HOST or GL_HOST: specify the url of the gitlab server if self hosted (eg: https://gitlab.example.com). Default is https://gitlab.com.
-  
-  REMOTE_ALIAS or GIT_REMOTE_URL_VAR: git remote variable or alias that contains the gitlab url.
-  Can be set in the config with 'glab config set remote_alias origin'
-  
-  VISUAL, EDITOR (in order of precedence): the editor tool to use for authoring text.
-  Can be set in the config with 'glab config set editor vim'
-  
-  BROWSER: the web browser to use for opening links.
-  Can be set in the config with 'glab config set browser mybrowser'
-  
-  GLAMOUR_STYLE: environment variable to set your desired markdown renderer style
-  Available options are (dark|light|notty) or set a custom style
-  https://github.com/charmbracelet/glamour#styles
-  
-  NO_PROMPT: set to 1 (true) or 0 (false) to disable and enable prompts respectively
-  
-  NO_COLOR: set to any value to avoid printing ANSI escape sequences for color output.
-  
-  FORCE_HYPERLINKS: set to 1 to force hyperlinks to be output, even when not outputing to a TTY
-  
-  GLAB_CONFIG_DIR: set to a directory path to override the global configuration location 
-
-LEARN MORE
-  Use 'glab <command> <subcommand> --help' for more information about a command.
-
-FEEDBACK
-  Encountered a bug or want to suggest a feature?
-  Open an issue using 'glab issue create -R profclems/glab'";
-
-    const SYSTEMCTL_HELP: &str = "
-systemctl [OPTIONS...] COMMAND ...
-
-Query or send control commands to the system manager.
-
-Unit Commands:
-  list-units [PATTERN...]             List units currently in memory
-  list-automounts [PATTERN...]        List automount units currently in memory,
-                                      ordered by path
-  list-paths [PATTERN...]             List path units currently in memory,
-                                      ordered by path
-  list-sockets [PATTERN...]           List socket units currently in memory,
-                                      ordered by address
-  list-timers [PATTERN...]            List timer units currently in memory,
-                                      ordered by next elapse
-  is-active PATTERN...                Check whether units are active
-  is-failed [PATTERN...]              Check whether units are failed or
-                                      system is in degraded state
-  status [PATTERN...|PID...]          Show runtime status of one or more units
-  show [PATTERN...|JOB...]            Show properties of one or more
-                                      units/jobs or the manager
-  cat PATTERN...                      Show files and drop-ins of specified units
-  help PATTERN...|PID...              Show manual for one or more units
-  list-dependencies [UNIT...]         Recursively show units which are required
-                                      or wanted by the units or by which those
-                                      units are required or wanted
-  start UNIT...                       Start (activate) one or more units
-  stop UNIT...                        Stop (deactivate) one or more units
-  reload UNIT...                      Reload one or more units
-  restart UNIT...                     Start or restart one or more units
-  try-restart UNIT...                 Restart one or more units if active
-  reload-or-restart UNIT...           Reload one or more units if possible,
-                                      otherwise start or restart
-  try-reload-or-restart UNIT...       If active, reload one or more units,
-                                      if supported, otherwise restart
-  isolate UNIT                        Start one unit and stop all others
-  kill UNIT...                        Send signal to processes of a unit
-  clean UNIT...                       Clean runtime, cache, state, logs or
-                                      configuration of unit
-  freeze PATTERN...                   Freeze execution of unit processes
-  thaw PATTERN...                     Resume execution of a frozen unit
-  set-property UNIT PROPERTY=VALUE... Sets one or more properties of a unit
-  bind UNIT PATH [PATH]               Bind-mount a path from the host into a
-                                      unit's namespace
-  mount-image UNIT PATH [PATH [OPTS]] Mount an image from the host into a
-                                      unit's namespace
-  service-log-level SERVICE [LEVEL]   Get/set logging threshold for service
-  service-log-target SERVICE [TARGET] Get/set logging target for service
-  reset-failed [PATTERN...]           Reset failed state for all, one, or more
-                                      units
-  whoami [PID...]                     Return unit caller or specified PIDs are
-                                      part of
-
-Unit File Commands:
-  list-unit-files [PATTERN...]        List installed unit files
-  enable [UNIT...|PATH...]            Enable one or more unit files
-  disable UNIT...                     Disable one or more unit files
-  reenable UNIT...                    Reenable one or more unit files
-  preset UNIT...                      Enable/disable one or more unit files
-                                      based on preset configuration
-  preset-all                          Enable/disable all unit files based on
-                                      preset configuration
-  is-enabled UNIT...                  Check whether unit files are enabled
-  mask UNIT...                        Mask one or more units
-  unmask UNIT...                      Unmask one or more units
-  link PATH...                        Link one or more units files into
-                                      the search path
-  revert UNIT...                      Revert one or more unit files to vendor
-                                      version
-  add-wants TARGET UNIT...            Add 'Wants' dependency for the target
-                                      on specified one or more units
-  add-requires TARGET UNIT...         Add 'Requires' dependency for the target
-                                      on specified one or more units
-  edit UNIT...                        Edit one or more unit files
-  get-default                         Get the name of the default target
-  set-default TARGET                  Set the default target
-
-Machine Commands:
-  list-machines [PATTERN...]          List local containers and host
-
-Job Commands:
-  list-jobs [PATTERN...]              List jobs
-  cancel [JOB...]                     Cancel all, one, or more jobs
-
-Environment Commands:
-  show-environment                    Dump environment
-  set-environment VARIABLE=VALUE...   Set one or more environment variables
-  unset-environment VARIABLE...       Unset one or more environment variables
-  import-environment VARIABLE...      Import all or some environment variables
-
-Manager State Commands:
-  daemon-reload                       Reload systemd manager configuration
-  daemon-reexec                       Reexecute systemd manager
-  log-level [LEVEL]                   Get/set logging threshold for manager
-  log-target [TARGET]                 Get/set logging target for manager
-  service-watchdogs [BOOL]            Get/set service watchdog state
-
-System Commands:
-  is-system-running                   Check whether system is fully running
-  default                             Enter system default mode
-  rescue                              Enter system rescue mode
-  emergency                           Enter system emergency mode
-  halt                                Shut down and halt the system
-  poweroff                            Shut down and power-off the system
-  reboot                              Shut down and reboot the system
-  kexec                               Shut down and reboot the system with kexec
-  soft-reboot                         Shut down and reboot userspace
-  exit [EXIT_CODE]                    Request user instance or container exit
-  switch-root [ROOT [INIT]]           Change to a different root file system
-  suspend                             Suspend the system
-  hibernate                           Hibernate the system
-  hybrid-sleep                        Hibernate and suspend the system
-  suspend-then-hibernate              Suspend the system, wake after a period of
-                                      time, and hibernate
-Options:
-  -h --help              Show this help
-     --version           Show package version
-     --system            Connect to system manager
-     --user              Connect to user service manager
-  -H --host=[USER@]HOST  Operate on remote host
-  -M --machine=CONTAINER Operate on a local container
-  -t --type=TYPE         List units of a particular type
-     --state=STATE       List units with particular LOAD or SUB or ACTIVE state
-     --failed            Shortcut for --state=failed
-  -p --property=NAME     Show only properties by this name
-  -P NAME                Equivalent to --value --property=NAME
-  -a --all               Show all properties/all units currently in memory,
-                         including dead/empty ones. To list all units installed
-                         on the system, use 'list-unit-files' instead.
-  -l --full              Don't ellipsize unit names on output
-  -r --recursive         Show unit list of host and local containers
-     --reverse           Show reverse dependencies with 'list-dependencies'
-     --with-dependencies Show unit dependencies with 'status', 'cat',
-                         'list-units', and 'list-unit-files'.
-     --job-mode=MODE     Specify how to deal with already queued jobs, when
-                         queueing a new job
-  -T --show-transaction  When enqueuing a unit job, show full transaction
-     --show-types        When showing sockets, explicitly show their type
-     --value             When showing properties, only print the value
-     --check-inhibitors=MODE
-                         Whether to check inhibitors before shutting down,
-                         sleeping, or hibernating
-  -i                     Shortcut for --check-inhibitors=no
-     --kill-whom=WHOM    Whom to send signal to
-     --kill-value=INT    Signal value to enqueue
-  -s --signal=SIGNAL     Which signal to send
-     --what=RESOURCES    Which types of resources to remove
-     --now               Start or stop unit after enabling or disabling it
-     --dry-run           Only print what would be done
-                         Currently supported by verbs: halt, poweroff, reboot,
-                             kexec, soft-reboot, suspend, hibernate, 
-                             suspend-then-hibernate, hybrid-sleep, default,
-                             rescue, emergency, and exit.
-  -q --quiet             Suppress output
-     --no-warn           Suppress several warnings shown by default
-     --wait              For (re)start, wait until service stopped again
-                         For is-system-running, wait until startup is completed
-     --no-block          Do not wait until operation finished
-     --no-wall           Don't send wall message before halt/power-off/reboot
-     --no-reload         Don't reload daemon after en-/dis-abling unit files
-     --legend=BOOL       Enable/disable the legend (column headers and hints)
-     --no-pager          Do not pipe output into a pager
-     --no-ask-password   Do not ask for system passwords
-     --global            Edit/enable/disable/mask default user unit files
-                         globally
-     --runtime           Edit/enable/disable/mask unit files temporarily until
-                         next reboot
-  -f --force             When enabling unit files, override existing symlinks
-                         When shutting down, execute action immediately
-     --preset-mode=      Apply only enable, only disable, or all presets
-     --root=PATH         Edit/enable/disable/mask unit files in the specified
-                         root directory
-     --image=PATH        Edit/enable/disable/mask unit files in the specified
-                         disk image
-     --image-policy=POLICY
-                         Specify disk image dissection policy
-  -n --lines=INTEGER     Number of journal entries to show
-  -o --output=STRING     Change journal output mode (short, short-precise,
-                             short-iso, short-iso-precise, short-full,
-                             short-monotonic, short-unix, short-delta,
-                             verbose, export, json, json-pretty, json-sse, cat)
-     --firmware-setup    Tell the firmware to show the setup menu on next boot
-     --boot-loader-menu=TIME
-                         Boot into boot loader menu on next boot
-     --boot-loader-entry=NAME
-                         Boot into a specific boot loader entry on next boot
-     --plain             Print unit dependencies as a list instead of a tree
-     --timestamp=FORMAT  Change format of printed timestamps (pretty, unix,
-                             us, utc, us+utc)
-     --read-only         Create read-only bind mount
-     --mkdir             Create directory before mounting, if missing
-     --marked            Restart/reload previously marked units
-     --drop-in=NAME      Edit unit files using the specified drop-in file name
-     --when=TIME         Schedule halt/power-off/reboot/kexec action after
-                         a certain timestamp
-
-See the systemctl(1) man page for details.";
-
-    const LAKE_HELP: &str = r#"
-Lake version 5.0.0-src+d8b1897 (Lean version 4.33.0)
-
-USAGE:
-  lake [OPTIONS] <COMMAND>
-
-COMMANDS:
-  new <name> <temp>     create a Lean package in a new directory
-  init <name> <temp>    create a Lean package in the current directory
-  build <targets>...    build targets
-  query <targets>...    build targets and output results
-  exe <exe> <args>...   build an exe and run it in Lake's environment
-  check-build           check if any default build targets are configured
-  test                  test the package using the configured test driver
-  check-test            check if there is a properly configured test driver
-  lint                  lint the package
-  check-lint            check if there is a properly configured lint driver
-  clean                 remove build outputs
-  shake                 minimize imports in source files
-  env <cmd> <args>...   execute a command in Lake's environment
-  lean <file>           elaborate a Lean file in Lake's context
-  update                update dependencies and save them to the manifest
-  pack                  pack build artifacts into an archive for distribution
-  unpack                unpack build artifacts from an distributed archive
-  upload <tag>          upload build artifacts to a GitHub release
-  cache                 manage the Lake cache
-  script                manage and run workspace scripts
-  scripts               shorthand for `lake script list`
-  run <script>          shorthand for `lake script run`
-  translate-config      change language of the package configuration
-  serve                 start the Lean language server
-
-BASIC OPTIONS:
-  --version             print version and exit
-  --help, -h            print help of the program or a command and exit
-  --dir, -d=file        use the package configuration in a specific directory
-  --file, -f=file       use a specific file for the package configuration
-  -K key[=value]        set the configuration file option named key
-  --old                 only rebuild modified modules (ignore transitive deps)
-  --rehash, -H          hash all files for traces (do not trust `.hash` files)
-  --update              update dependencies on load (e.g., before a build)
-  --packages=file       JSON file of package entries that override the manifest
-  --reconfigure, -R     elaborate configuration files instead of using OLeans
-  --keep-toolchain      do not update toolchain on workspace update
-  --allow-empty         accept bare builds with no default targets configured
-  --no-build            exit immediately if a build target is not up-to-date
-  --no-cache            build packages locally; do not download build caches
-  --try-cache           attempt to download build caches for supported packages
-  --json, -J            output JSON-formatted results (in `lake query`)
-  --text                output results as plain text (in `lake query`)
-
-OUTPUT OPTIONS:
-  --quiet, -q           hide informational logs and the progress indicator
-  --verbose, -v         show trace logs (command invocations) and built targets
-  --ansi, --no-ansi     toggle the use of ANSI escape codes to prettify output
-  --log-level=lv        minimum log level to output on success
-                        (levels: trace, info, warning, error)
-  --fail-level=lv       minimum log level to fail a build (default: error)
-  --iofail              fail build if any I/O or other info is logged
-                        (same as --fail-level=info)
-  --wfail               fail build if warnings are logged
-                        (same as --fail-level=warning)
-
-
-See `lake help <command>` for more information on a specific command."#;
+    use super::{
+        Cmd, Cmds, ConfigFile, ExtractError, Flag, Helper, check_compiled, check_top_level,
+        deconflict, extract, extract_text, flag_name,
+    };
+
+    /// Replay the help text recorded in `tests/help`, see [`Helper::replay`]
+    fn help(args: &[&str]) -> String {
+        let helper = Helper {
+            replay: Some(PathBuf::from("tests/help")),
+            ..Helper::default()
+        };
+        let args = args.iter().map(|a| String::from(*a)).collect::<Vec<_>>();
+        helper.help(&args).unwrap().unwrap()
+    }
 
     #[allow(clippy::type_complexity)]
     fn go(conf: &ConfigFile, s: String) -> (Vec<(String, String)>, Vec<(String, String)>) {
-        let (flags, subs) = extract_text(conf, s);
+        let (flags, subs) = extract_text(conf, None, s).unwrap();
         let mut subs = subs
             .0
             .iter()
@@ -1454,7 +956,7 @@ See `lake help <command>` for more information on a specific command."#;
         subs.sort();
         let mut flags = flags
             .into_iter()
-            .map(|(k, v)| (v.short, k.strip_prefix("--").unwrap().to_string()))
+            .map(|(k, v)| (v.short, String::from(flag_name(&k))))
             .collect::<Vec<_>>();
         flags.sort();
         (flags, subs)
@@ -1473,8 +975,8 @@ See `lake help <command>` for more information on a specific command."#;
 
     #[test]
     fn extract_cabal() {
-        let conf = ConfigFile::from_file(PathBuf::from("conf/cabal.toml"));
-        let (flags, subs) = go(&conf, String::from(CABAL_HELP));
+        let conf = ConfigFile::from_file(PathBuf::from("conf/cabal.toml")).unwrap();
+        let (flags, subs) = go(&conf, help(&["cabal"]));
         let expected = expect![[r#"
             a -> active-repositories
             c- -> config-file
@@ -1554,8 +1056,8 @@ See `lake help <command>` for more information on a specific command."#;
 
     #[test]
     fn extract_cargo() {
-        let conf = ConfigFile::from_file(PathBuf::from("conf/cargo.toml"));
-        let (flags, subs) = go(&conf, String::from(CARGO_HELP));
+        let conf = ConfigFile::from_file(PathBuf::from("conf/cargo.toml")).unwrap();
+        let (flags, subs) = go(&conf, help(&["cargo"]));
         let expected = expect![[r#"
             cg -> config
             col -> color
@@ -1628,7 +1130,7 @@ See `lake help <command>` for more information on a specific command."#;
             extract_subs: true,
             ..ConfigFile::default()
         };
-        let (flags, subs) = go(&conf, String::from(DOCKER_HELP));
+        let (flags, subs) = go(&conf, help(&["docker"]));
         let expected = expect![[r#"
             cg -> config
             ct -> context
@@ -1714,7 +1216,7 @@ See `lake help <command>` for more information on a specific command."#;
             extract_subs: true,
             ..ConfigFile::default()
         };
-        let (flags, subs) = go(&conf, String::from(ELAN_HELP));
+        let (flags, subs) = go(&conf, help(&["elan"]));
         let expected = expect![[r#"
             hp -> help
             vb -> verbose
@@ -1736,11 +1238,99 @@ See `lake help <command>` for more information on a specific command."#;
         "#]];
         expected.assert_eq(&serialize(&subs));
     }
+
+    #[test]
+    fn extract_cargo_descriptions() {
+        let conf = ConfigFile::from_file(PathBuf::from("conf/cargo.toml")).unwrap();
+        let (flags, _subs) = extract_text(&conf, None, help(&["cargo"])).unwrap();
+        assert_eq!(
+            flags.get("--frozen").and_then(|f| f.desc.as_deref()),
+            Some("Require Cargo.lock and cache are up to date")
+        );
+        assert_eq!(
+            flags.get("--explain").and_then(|f| f.desc.as_deref()),
+            Some("Run `rustc --explain CODE`")
+        );
+    }
+
+    #[test]
+    fn extract_arity() {
+        let conf = ConfigFile::from_file(PathBuf::from("conf/cargo.toml")).unwrap();
+        let (flags, _subs) = extract_text(&conf, None, help(&["cargo"])).unwrap();
+        let arity = |f: &str| flags.get(f).map(|f| (f.arity, f.alias.as_deref()));
+        assert_eq!(arity("--color"), Some((1, None)));
+        assert_eq!(arity("--config"), Some((1, None)));
+        assert_eq!(arity("--verbose"), Some((0, Some("-v"))));
+        assert_eq!(arity("--frozen"), Some((0, None)));
+
+        let conf = ConfigFile {
+            extract_flags: true,
+            ..ConfigFile::default()
+        };
+        let (flags, _subs) = extract_text(&conf, None, help(&["docker"])).unwrap();
+        let arity = |f: &str| flags.get(f).map(|f| (f.arity, f.alias.as_deref()));
+        assert_eq!(arity("--context"), Some((1, Some("-c"))));
+        assert_eq!(arity("--debug"), Some((0, Some("-D"))));
+        assert_eq!(arity("--tls"), Some((0, None)));
+
+        // e.g., `[-p | --paginate | -P | --no-pager]` in a usage line
+        let conf = ConfigFile {
+            extract_flags: true,
+            ..ConfigFile::default()
+        };
+        let (flags, _subs) = extract_text(&conf, None, help(&["git"])).unwrap();
+        assert_eq!(flags.get("--paginate").map(|f| f.arity), Some(0));
+
+        // The config overrides the guess, even with 0
+        let conf: ConfigFile = toml::from_str(
+            r#"
+            extract_flags = true
+            [flags.--color]
+            short = "co"
+            arity = 0
+            [flags.-C]
+            short = "C"
+            arity = 1
+            "#,
+        )
+        .unwrap();
+        let (flags, _subs) = extract_text(&conf, None, help(&["cargo"])).unwrap();
+        let arity = |f: &str| flags.get(f).map(|f| (f.short.as_str(), f.arity));
+        assert_eq!(arity("--color"), Some(("co", 0)));
+        assert_eq!(arity("-C"), Some(("C", 1)));
+    }
+
+    #[test]
+    fn extract_git_descriptions() {
+        let conf = ConfigFile::from_file(PathBuf::from("conf/git.toml")).unwrap();
+        let (flags, subs) = extract_text(&conf, None, help(&["git"])).unwrap();
+        let cmds = Cmds(BTreeMap::from([(
+            String::from("git"),
+            Cmd {
+                short: String::from("g"),
+                desc: None,
+                flags,
+                no_args: None,
+                subs,
+                values: None,
+                disable: None,
+            },
+        )]));
+        assert_eq!(
+            cmds.describe("git clone "),
+            Some("Clone a repository into a new directory")
+        );
+        // Only in `extra_subs`, not in the help text
+        assert_eq!(cmds.describe("git bugreport "), None);
+        assert_eq!(cmds.describe("git nonexistent "), None);
+    }
+
     #[test]
     fn extract_git() {
-        let conf = ConfigFile::from_file(PathBuf::from("conf/git.toml"));
-        let (flags, subs) = go(&conf, String::from(GIT_HELP));
+        let conf = ConfigFile::from_file(PathBuf::from("conf/git.toml")).unwrap();
+        let (flags, subs) = go(&conf, help(&["git"]));
         let expected = expect![[r#"
+            C -> -C
             pg -> paginate
             vs -> version
         "#]];
@@ -1753,6 +1343,7 @@ See `lake help <command>` for more information on a specific command."#;
             arm -> archimport
             arv -> archive
             b -> branch
+            ba -> backfill
             bg -> bugreport
             bi -> bisect
             bl -> blame
@@ -1900,7 +1491,7 @@ See `lake help <command>` for more information on a specific command."#;
             extract_subs: true,
             ..ConfigFile::default()
         };
-        let (flags, subs) = go(&conf, String::from(GIT_SUBMODULE_HELP));
+        let (flags, subs) = go(&conf, help(&["git", "submodule"]));
         let expected = expect![[r#"
             al -> all
             bh -> branch
@@ -1957,7 +1548,7 @@ See `lake help <command>` for more information on a specific command."#;
             extract_subs: true,
             ..ConfigFile::default()
         };
-        let (flags, subs) = go(&conf, String::from(GLAB_HELP));
+        let (flags, subs) = go(&conf, help(&["glab"]));
         let expected = expect![[r#"
             hp -> help
             ve -> version
@@ -1991,7 +1582,7 @@ See `lake help <command>` for more information on a specific command."#;
             extract_subs: true,
             ..ConfigFile::default()
         };
-        let (flags, subs) = go(&conf, String::from(LAKE_HELP));
+        let (flags, subs) = go(&conf, help(&["lake"]));
         let expected = expect![[r#"
             al -> allow-empty
             an -> ansi
@@ -2026,8 +1617,8 @@ See `lake help <command>` for more information on a specific command."#;
     #[test]
     #[ignore] // TODO: flaky
     fn extract_systemctl() {
-        let conf = ConfigFile::from_file(PathBuf::from("conf/systemctl.toml"));
-        let (flags, subs) = go(&conf, String::from(SYSTEMCTL_HELP));
+        let conf = ConfigFile::from_file(PathBuf::from("conf/systemctl.toml")).unwrap();
+        let (flags, subs) = go(&conf, help(&["systemctl"]));
         let expected = expect![[r#"
             al -> all
             be -> boot-loader-entry
@@ -2166,30 +1757,279 @@ See `lake help <command>` for more information on a specific command."#;
     #[test]
     fn test_deconflict() {
         let conf = ConfigFile::default();
-        assert_eq!(deconflict(&conf, &[], &[], &[]), HashMap::new());
         assert_eq!(
-            deconflict(&conf, &[String::from("foo")], &[], &[]),
+            deconflict(&conf, None, &[], &[], &[]).unwrap(),
+            HashMap::new()
+        );
+        assert_eq!(
+            deconflict(&conf, None, &[String::from("foo")], &[], &[]).unwrap(),
             HashMap::from([(String::from("foo"), String::from("f"))])
         );
         assert_eq!(
-            deconflict(&conf, &[String::from("bar"), String::from("baz")], &[], &[]),
+            deconflict(
+                &conf,
+                None,
+                &[String::from("bar"), String::from("baz")],
+                &[],
+                &[]
+            )
+            .unwrap(),
             HashMap::from([
                 (String::from("bar"), String::from("br")),
                 (String::from("baz"), String::from("bz"))
             ])
         );
-        let conf = ConfigFile::from_file(PathBuf::from("conf/git.toml"));
+        let previous = Cmd {
+            short: String::from("f"),
+            desc: None,
+            flags: HashMap::from([(
+                String::from("--baz"),
+                Flag {
+                    short: String::from("b"),
+                    ..Flag::default()
+                },
+            )]),
+            no_args: None,
+            subs: Cmds::default(),
+            values: None,
+            disable: None,
+        };
+        assert_eq!(
+            deconflict(
+                &conf,
+                Some(&previous),
+                &[String::from("bar"), String::from("baz")],
+                &[],
+                &[]
+            )
+            .unwrap(),
+            HashMap::from([
+                (String::from("bar"), String::from("ba")),
+                (String::from("baz"), String::from("b"))
+            ])
+        );
+        let previous = Cmd {
+            flags: HashMap::from([(
+                String::from("--version"),
+                Flag {
+                    short: String::from("vern"),
+                    ..Flag::default()
+                },
+            )]),
+            subs: Cmds(BTreeMap::from([(
+                String::from("version"),
+                Cmd {
+                    short: String::from("vern"),
+                    desc: None,
+                    flags: HashMap::new(),
+                    no_args: None,
+                    subs: Cmds::default(),
+                    values: None,
+                    disable: None,
+                },
+            )])),
+            ..previous
+        };
         assert_eq!(
             deconflict(
                 &conf,
+                Some(&previous),
+                &[String::from("verbose"), String::from("version")],
+                &[String::from("version")],
+                &[]
+            )
+            .unwrap(),
+            HashMap::from([
+                (String::from("verbose"), String::from("v")),
+                (String::from("version"), String::from("vern"))
+            ])
+        );
+        let conf = ConfigFile::from_file(PathBuf::from("conf/git.toml")).unwrap();
+        assert_eq!(
+            deconflict(
+                &conf,
+                None,
                 &[String::from("show"), String::from("status")],
                 &[],
                 &[]
-            ),
+            )
+            .unwrap(),
             HashMap::from([
                 (String::from("status"), String::from("st")),
                 (String::from("show"), String::from("sh"))
             ])
         );
     }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            ConfigFile::from_file("conf/nonexistent.toml"),
+            Err(ExtractError::Io(..))
+        ));
+        let conf: ConfigFile = toml::from_str(
+            r#"
+            [subs.foo]
+            short = "f"
+            [subs.bar]
+            short = "f"
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            deconflict(
+                &conf,
+                None,
+                &[],
+                &[String::from("bar"), String::from("foo")],
+                &[]
+            ),
+            Err(ExtractError::InvalidConfig(_))
+        ));
+        let tools = vec![(String::new(), ConfigFile::default(), None)];
+        assert!(matches!(
+            extract(&Helper::default(), 1, tools),
+            Err(ExtractError::InvalidConfig(_))
+        ));
+        let conf: ConfigFile = toml::from_str(r#"exact_subs = [""]"#).unwrap();
+        let tools = vec![(String::from("git"), conf, None)];
+        assert!(matches!(
+            extract(&Helper::default(), 1, tools),
+            Err(ExtractError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_extract_tree() {
+        let conf: ConfigFile = toml::from_str(
+            r#"
+            short = "g"
+            exact_subs = ["status", "submodule"]
+            [subs.submodule]
+            short = "su"
+            exact_subs = ["init", "update"]
+            [subs.submodule.subs.update.flags.--init]
+            short = "i"
+            squish = true
+            "#,
+        )
+        .unwrap();
+        let tools = vec![
+            (String::from("git"), conf, None),
+            (String::from("other"), ConfigFile::default(), None),
+        ];
+        let extracted = extract(&Helper::default(), 4, tools).unwrap();
+        let gen_conf = crate::zle::expand::ConfigFile {
+            cmds: Cmds(extracted),
+            ..Default::default()
+        };
+        let expected = expect![[r#"
+            [cmds.git]
+            short = "g"
+
+            [cmds.git.subs.status]
+            short = "s"
+
+            [cmds.git.subs.submodule]
+            short = "su"
+
+            [cmds.git.subs.submodule.subs.init]
+            short = "i"
+
+            [cmds.git.subs.submodule.subs.update]
+            short = "u"
+
+            [cmds.git.subs.submodule.subs.update.flags.--init]
+            short = "i"
+            squish = true
+
+            [cmds.other]
+            short = "o"
+        "#]];
+        expected.assert_eq(&toml::to_string(&gen_conf).unwrap());
+    }
+
+    #[test]
+    fn test_extract_replay() {
+        let conf = ConfigFile::from_file(PathBuf::from("conf/git.toml")).unwrap();
+        let helper = Helper {
+            replay: Some(PathBuf::from("tests/help")),
+            ..Helper::default()
+        };
+        let tools = vec![(String::from("git"), conf, None)];
+        let cmds = Cmds(extract(&helper, 4, tools).unwrap());
+        assert_eq!(
+            cmds.describe("git clone "),
+            Some("Clone a repository into a new directory")
+        );
+        let submodule = &cmds.0["git"].subs.0["submodule"];
+        assert_eq!(submodule.short, "su");
+        assert!(submodule.subs.0["update"].flags.contains_key("--init"));
+    }
+
+    #[test]
+    fn test_check_top_level() {
+        let cmds = |toml: &str| toml::from_str::<crate::zle::expand::ConfigFile>(toml).unwrap();
+        let ok = cmds(
+            r#"
+            [cmds.cabal]
+            short = "cb"
+            [cmds.cargo]
+            short = "cg"
+            "#,
+        );
+        assert!(check_top_level(&ok.cmds).is_ok());
+        let same = cmds(
+            r#"
+            [cmds.cabal]
+            short = "c"
+            [cmds.cargo]
+            short = "c"
+            "#,
+        );
+        assert!(matches!(
+            check_top_level(&same.cmds),
+            Err(ExtractError::Conflict(..))
+        ));
+        let shadow = cmds(
+            r#"
+            [cmds.gh]
+            short = "g"
+            [cmds.git]
+            short = "gh"
+            "#,
+        );
+        assert!(matches!(
+            check_top_level(&shadow.cmds),
+            Err(ExtractError::Conflict(..))
+        ));
+    }
+
+    #[test]
+    fn test_check_compiled() {
+        let toml = r#"
+            [cmds.gh]
+            short = "gh"
+            [cmds.gh.subs.org]
+            short = "o"
+            [cmds.git]
+            short = "g"
+            [cmds.git.subs.hook]
+            short = "ho"
+            "#;
+        let cmds = |toml: &str| {
+            toml::from_str::<crate::zle::expand::ConfigFile>(toml)
+                .unwrap()
+                .cmds
+        };
+        let Err(ExtractError::Conflict(key, first, second)) = check_compiled(&cmds(toml)) else {
+            panic!("no conflict");
+        };
+        assert_eq!(key, "gho");
+        let mut commands = [first, second];
+        commands.sort();
+        assert_eq!(commands, ["gh org", "git hook"]);
+        let fixed = toml.replace(r#"short = "o""#, r#"short = "or""#);
+        assert!(check_compiled(&cmds(&fixed)).is_ok());
+    }
 }
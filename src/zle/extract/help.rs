@@ -0,0 +1,269 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, warn};
+
+use super::ExtractError;
+
+/// How to run `--help` for commands and subcommands
+#[derive(Debug)]
+pub(crate) struct Helper {
+    pub(super) timeout: Duration,
+    /// Use stderr when the command fails or prints nothing to stdout
+    pub(super) stderr: bool,
+    /// Save help text to this directory
+    pub(super) record: Option<PathBuf>,
+    /// Read help text from this directory instead of running commands
+    pub(super) replay: Option<PathBuf>,
+}
+
+impl Default for Helper {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            stderr: false,
+            record: None,
+            replay: None,
+        }
+    }
+}
+
+/// Where the help text for `args` is recorded in `dir`. Path separators are
+/// escaped, so that each recording is a file directly in `dir`.
+fn fixture(dir: &Path, args: &[String]) -> PathBuf {
+    let name = args
+        .join(" ")
+        .replace('%', "%25")
+        .replace('/', "%2F")
+        .replace('\\', "%5C");
+    dir.join(format!("{name}.txt"))
+}
+
+fn read_all<R: Read + Send + 'static>(r: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut r) = r {
+            drop(r.read_to_end(&mut buf));
+        }
+        buf
+    })
+}
+
+/// Remove ANSI escape sequences, e.g., colors.
+pub(super) fn strip_ansi(s: &str) -> String {
+    let mut r = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            r.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI: parameters and intermediates, then a final byte
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC: terminated by BEL or ST (ESC \)
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    r
+}
+
+impl Helper {
+    pub(super) fn help(&self, args: &[String]) -> Result<Option<String>, ExtractError> {
+        if let Some(dir) = &self.replay {
+            let path = fixture(dir, args);
+            debug!("Replaying {}", path.display());
+            return match fs::read_to_string(&path) {
+                Ok(h) => Ok(Some(h)),
+                // Not recorded because `--help` failed
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(ExtractError::Io(e, path)),
+            };
+        }
+        let h = self.run(args)?;
+        if let (Some(dir), Some(h)) = (&self.record, &h) {
+            let path = fixture(dir, args);
+            debug!("Recording {}", path.display());
+            fs::create_dir_all(dir).map_err(|e| ExtractError::Io(e, dir.clone()))?;
+            fs::write(&path, h).map_err(|e| ExtractError::Io(e, path))?;
+        }
+        Ok(h)
+    }
+
+    fn run(&self, args: &[String]) -> Result<Option<String>, ExtractError> {
+        let mut builder = Command::new(&args[0]);
+        builder
+            .args(&args[1..])
+            .arg("--help")
+            .env("PAGER", "cat")
+            .env("NO_COLOR", "1")
+            .env("TERM", "dumb")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        debug!("Running {builder:?}");
+        let mut child = builder.spawn().map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                ExtractError::MissingExecutable(args[0].clone())
+            } else {
+                ExtractError::Run(e, args.join(" "))
+            }
+        })?;
+        let stdout = read_all(child.stdout.take());
+        let stderr = read_all(child.stderr.take());
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child
+                .try_wait()
+                .map_err(|e| ExtractError::Run(e, args.join(" ")))?
+            {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                // Don't wait on the readers, grandchildren may hold the pipes
+                drop(child.kill());
+                drop(child.wait());
+                warn!("Timed out running `{} --help`", args.join(" "));
+                return Ok(None);
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+
+        let out = if status.success() && !stdout.is_empty() {
+            stdout
+        } else if self.stderr && !stderr.is_empty() {
+            debug!("Using stderr of `{} --help`", args.join(" "));
+            stderr
+        } else if status.success() {
+            stdout
+        } else {
+            return Ok(None);
+        };
+        let out = String::from_utf8(out).map_err(|_| ExtractError::NonUtf8(args.join(" ")))?;
+        Ok(Some(strip_ansi(&out)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::{Helper, fixture, strip_ansi};
+    use crate::zle::dirs::TempDir;
+    use crate::zle::extract::ExtractError;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec![String::from("sh"), String::from("-c"), String::from(script)]
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("plain"), "plain");
+        assert_eq!(
+            strip_ansi("\x1b[1;32mbuild\x1b[0m  Compile"),
+            "build  Compile"
+        );
+        assert_eq!(
+            strip_ansi("\x1b]8;;https://x\x07link\x1b]8;;\x1b\\"),
+            "link"
+        );
+    }
+
+    #[test]
+    fn test_help() {
+        let helper = Helper {
+            timeout: Duration::from_millis(200),
+            ..Helper::default()
+        };
+        assert!(matches!(
+            helper.help(&[String::from("zbr-nonexistent-executable")]),
+            Err(ExtractError::MissingExecutable(_))
+        ));
+        assert_eq!(
+            helper.help(&sh("echo usage")).unwrap().as_deref(),
+            Some("usage\n")
+        );
+        assert_eq!(helper.help(&sh("sleep 5")).unwrap(), None);
+        assert_eq!(helper.help(&sh("echo usage >&2; exit 1")).unwrap(), None);
+        let helper = Helper {
+            stderr: true,
+            ..helper
+        };
+        assert_eq!(
+            helper
+                .help(&sh("echo usage >&2; exit 1"))
+                .unwrap()
+                .as_deref(),
+            Some("usage\n")
+        );
+    }
+
+    #[test]
+    fn test_fixture() {
+        let dir = Path::new("help");
+        assert_eq!(
+            fixture(dir, &[String::from("git"), String::from("submodule")]),
+            Path::new("help/git submodule.txt")
+        );
+        assert_eq!(
+            fixture(dir, &sh("cat ../50%")),
+            Path::new("help/sh -c cat ..%2F50%25.txt")
+        );
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let dir = TempDir::new("record");
+        let recorder = Helper {
+            record: Some(dir.to_path_buf()),
+            ..Helper::default()
+        };
+        let replayer = Helper {
+            replay: Some(dir.to_path_buf()),
+            ..Helper::default()
+        };
+        assert_eq!(
+            recorder.help(&sh("echo usage")).unwrap().as_deref(),
+            Some("usage\n")
+        );
+        assert_eq!(recorder.help(&sh("exit 1")).unwrap(), None);
+        assert_eq!(
+            recorder.help(&sh("echo usage; : ../x")).unwrap().as_deref(),
+            Some("usage\n")
+        );
+        assert_eq!(
+            replayer.help(&sh("echo usage")).unwrap().as_deref(),
+            Some("usage\n")
+        );
+        assert_eq!(
+            replayer.help(&sh("echo usage; : ../x")).unwrap().as_deref(),
+            Some("usage\n")
+        );
+        assert_eq!(replayer.help(&sh("exit 1")).unwrap(), None);
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+use std::env::consts::OS;
+use std::path::{Path, PathBuf};
+
+use tracing::{info, warn};
+
+use super::{ConfigFile, ExtractError};
+use crate::zle::path::find_executable;
+
+/// Tools to extract in one go
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Manifest {
+    /// Where to write the generated config, relative to the manifest
+    pub(super) output: Option<PathBuf>,
+    #[serde(default)]
+    tools: BTreeMap<String, Tool>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Tool {
+    /// Extraction config, relative to the manifest
+    conf: Option<PathBuf>,
+    /// Only extract on these operating systems, e.g., `linux` or `macos`
+    #[serde(default)]
+    os: Vec<String>,
+    /// Skip the tool if it isn't installed, rather than failing
+    #[serde(default)]
+    optional: bool,
+}
+
+impl Tool {
+    fn applies(&self, long: &str) -> bool {
+        if !self.os.is_empty() && !self.os.iter().any(|os| os == OS) {
+            info!("Skipping {long}, which isn't extracted on {OS}");
+            return false;
+        }
+        if self.optional && find_executable(long).is_none() {
+            warn!("Skipping {long}, which isn't installed");
+            return false;
+        }
+        true
+    }
+}
+
+impl Manifest {
+    pub(super) fn from_file<P: AsRef<Path>>(p: P) -> Result<Self, ExtractError> {
+        let path = p.as_ref();
+        let s =
+            std::fs::read_to_string(path).map_err(|e| ExtractError::Io(e, path.to_path_buf()))?;
+        let mut manifest = toml::from_str::<Manifest>(&s)
+            .map_err(|e| ExtractError::Toml(e, path.to_path_buf()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        if let Some(output) = &mut manifest.output {
+            *output = dir.join(&output);
+        }
+        for tool in manifest.tools.values_mut() {
+            if let Some(conf) = &mut tool.conf {
+                *conf = dir.join(&conf);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// The extraction config for each tool that applies to this system
+    pub(super) fn configs(&self) -> Result<Vec<(String, ConfigFile)>, ExtractError> {
+        let mut configs = Vec::with_capacity(self.tools.len());
+        for (long, tool) in &self.tools {
+            if !tool.applies(long) {
+                continue;
+            }
+            let conf = match &tool.conf {
+                Some(p) => ConfigFile::from_file(p)?,
+                None => ConfigFile::default(),
+            };
+            configs.push((long.clone(), conf));
+        }
+        Ok(configs)
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::VecDeque;
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+#[derive(Debug)]
+struct Queue<T> {
+    tasks: VecDeque<T>,
+    running: usize,
+    failed: bool,
+}
+
+#[derive(Debug)]
+struct Shared<T, E> {
+    queue: Mutex<Queue<T>>,
+    cv: Condvar,
+    error: Mutex<Option<E>>,
+}
+
+impl<T, E> Shared<T, E> {
+    fn lock(&self) -> MutexGuard<'_, Queue<T>> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn next(&self) -> Option<T> {
+        let mut q = self.lock();
+        loop {
+            if q.failed {
+                return None;
+            }
+            if let Some(task) = q.tasks.pop_front() {
+                q.running += 1;
+                return Some(task);
+            }
+            if q.running == 0 {
+                return None;
+            }
+            q = self.cv.wait(q).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn finish(&self, result: Result<Vec<T>, E>) {
+        let mut q = self.lock();
+        q.running -= 1;
+        match result {
+            Ok(more) => q.tasks.extend(more),
+            Err(e) => {
+                q.failed = true;
+                self.error
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .get_or_insert(e);
+            }
+        }
+        self.cv.notify_all();
+    }
+}
+
+/// Stops the other workers if a task panics, rather than leaving them waiting
+/// on it forever.
+struct Guard<'a, T, E>(&'a Shared<T, E>);
+
+impl<T, E> Drop for Guard<'_, T, E> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            let mut q = self.0.lock();
+            q.failed = true;
+            self.0.cv.notify_all();
+        }
+    }
+}
+
+/// Run `f` on each task using `jobs` threads. Each call to `f` may produce
+/// more tasks. Stops at the first error.
+pub(super) fn run<T: Send, E: Send>(
+    jobs: usize,
+    tasks: Vec<T>,
+    f: impl Fn(T) -> Result<Vec<T>, E> + Sync,
+) -> Result<(), E> {
+    let shared = Shared {
+        queue: Mutex::new(Queue {
+            tasks: VecDeque::from(tasks),
+            running: 0,
+            failed: false,
+        }),
+        cv: Condvar::new(),
+        error: Mutex::new(None),
+    };
+    thread::scope(|s| {
+        for _ in 0..jobs.max(1) {
+            s.spawn(|| {
+                while let Some(task) = shared.next() {
+                    let guard = Guard(&shared);
+                    let result = f(task);
+                    drop(guard);
+                    shared.finish(result);
+                }
+            });
+        }
+    });
+    match shared
+        .error
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+    {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::run;
+
+    #[test]
+    fn test_run() {
+        // Each task `n` produces `n` tasks `n - 1`
+        let count = AtomicUsize::new(0);
+        let r: Result<(), ()> = run(4, vec![3, 3], |n: usize| {
+            count.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![n.saturating_sub(1); n])
+        });
+        assert_eq!(r, Ok(()));
+        // 3 -> 3 * 2 -> 6 * 1 -> 6 * 0, twice
+        assert_eq!(count.load(Ordering::Relaxed), 2 * (1 + 3 + 6 + 6));
+
+        let r = run(
+            4,
+            vec![0, 1, 2],
+            |n: usize| if n == 1 { Err(n) } else { Ok(vec![]) },
+        );
+        assert_eq!(r, Err(1));
+    }
+}
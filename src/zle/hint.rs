@@ -1,24 +1,206 @@
-use super::compile::{self};
+use std::fmt::Write as _;
+
 use super::expand::{self, clean_buf};
+use super::rank::Ranking;
+
+/// How to print hints
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum Format {
+    /// Aligned columns
+    #[default]
+    Text,
+    /// Like `text`, with ANSI escapes for colors
+    Ansi,
+    /// Like `text`, preceded by `region_highlight` entries for it and a blank
+    /// line
+    Zsh,
+    /// An array of objects, for other frontends
+    Json,
+}
+
+/// The parts of a hint, which are highlighted differently
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Style {
+    Plain,
+    /// The part of the abbreviation that was already typed
+    Typed,
+    /// The rest of the abbreviation
+    Rest,
+    Expansion,
+    Description,
+}
+
+impl Style {
+    fn ansi(self) -> &'static str {
+        match self {
+            Style::Plain => "",
+            Style::Typed => "\x1b[4m",
+            Style::Rest => "\x1b[1m",
+            Style::Expansion => "\x1b[2m",
+            Style::Description => "\x1b[2;3m",
+        }
+    }
+
+    /// See "Character Highlighting" in `man zshzle`
+    fn zsh(self) -> &'static str {
+        match self {
+            Style::Plain => "",
+            Style::Typed => "underline",
+            Style::Rest => "bold",
+            Style::Expansion | Style::Description => "fg=8",
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Hint<'a> {
+    abbreviation: &'a str,
+    expansion: &'a str,
+    description: Option<&'a str>,
+    /// How many characters of the abbreviation were already typed
+    typed: usize,
+}
+
+type Line = Vec<(String, Style)>;
+
+fn len(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn line_len(line: &Line) -> usize {
+    line.iter().map(|(s, _)| len(s)).sum()
+}
+
+/// e.g., `gs  --> git status  Show the working tree status`, with the
+/// abbreviation padded to `key_width`, and the expansion to `value_width` if
+/// there's a description
+fn cell(h: &Hint<'_>, key_width: usize, value_width: Option<usize>) -> Line {
+    let split = h
+        .abbreviation
+        .char_indices()
+        .nth(h.typed)
+        .map_or(h.abbreviation.len(), |(i, _)| i);
+    let (typed, rest) = h.abbreviation.split_at(split);
+    let pad = " ".repeat(key_width - len(h.abbreviation));
+    let expansion = h.expansion.trim_end();
+    let mut line = vec![
+        (String::from(typed), Style::Typed),
+        (String::from(rest), Style::Rest),
+        (format!("{pad} --> "), Style::Plain),
+        (String::from(expansion), Style::Expansion),
+    ];
+    if let Some(width) = value_width
+        && let Some(desc) = h.description
+    {
+        let pad = " ".repeat(width - len(expansion) + 2);
+        line.push((pad, Style::Plain));
+        line.push((String::from(desc), Style::Description));
+    }
+    line.retain(|(s, _)| !s.is_empty());
+    line
+}
+
+/// One hint per line, or if none of them have descriptions, as many per line as
+/// fit in `width`
+fn layout(hints: &[Hint<'_>], width: Option<usize>) -> Vec<Line> {
+    let key_width = hints.iter().map(|h| len(h.abbreviation)).max();
+    let value_width = hints.iter().map(|h| len(h.expansion.trim_end())).max();
+    let (Some(key_width), Some(value_width)) = (key_width, value_width) else {
+        return Vec::new();
+    };
+    let cell_width = key_width + " --> ".len() + value_width;
+    let columns = width.map_or(1, |w| (w + 2) / (cell_width + 2));
+    if columns <= 1 || hints.len() == 1 || hints.iter().any(|h| h.description.is_some()) {
+        return hints
+            .iter()
+            .map(|h| cell(h, key_width, Some(value_width)))
+            .collect();
+    }
+    let mut lines = Vec::new();
+    for row in hints.chunks(columns) {
+        let mut line = Line::new();
+        for h in row {
+            if !line.is_empty() {
+                let pad = (cell_width + 2) - (line_len(&line) % (cell_width + 2));
+                line.push((" ".repeat(pad), Style::Plain));
+            }
+            line.extend(cell(h, key_width, None));
+        }
+        lines.push(line);
+    }
+    lines
+}
 
-pub(super) fn hint(conf: &expand::ConfigFile, buf: String, max: usize) -> Vec<(String, String)> {
+/// Print `hints` for `buf` in `format`, fitting them in `width` columns if
+/// given
+pub(super) fn render(
+    conf: &expand::ConfigFile,
+    buf: String,
+    hints: &[(String, String)],
+    format: Format,
+    width: Option<usize>,
+) -> String {
     let (_prefix, buf) = clean_buf(buf);
-    let mut compiled = compile::compile_with_prefixes(&conf.cmds, &buf, false)
-        .into_iter()
+    let hints = hints
+        .iter()
+        .map(|(k, v)| Hint {
+            abbreviation: k,
+            expansion: v,
+            description: conf.cmds.describe(v),
+            typed: if k.starts_with(&buf) { len(&buf) } else { 0 },
+        })
         .collect::<Vec<_>>();
-    compiled.sort();
-    compiled
-        .into_iter()
-        .filter(|(k, _v)| k.starts_with(&buf))
-        .take(max)
-        .collect()
+    let lines = layout(&hints, width);
+    let mut text = String::new();
+    let mut highlights = String::new();
+    for line in &lines {
+        for (s, style) in line {
+            match format {
+                Format::Ansi if *style != Style::Plain => {
+                    let _ = write!(text, "{}{s}\x1b[0m", style.ansi());
+                }
+                Format::Zsh if *style != Style::Plain => {
+                    let start = len(&text);
+                    let _ = writeln!(highlights, "{start} {} {}", start + len(s), style.zsh());
+                    text.push_str(s);
+                }
+                _ => text.push_str(s),
+            }
+        }
+        text.push('\n');
+    }
+    match format {
+        Format::Text | Format::Ansi => text,
+        Format::Zsh if text.is_empty() => text,
+        Format::Zsh => format!("{highlights}\n{text}"),
+        // Serializing these strings can't fail
+        Format::Json => serde_json::to_string(&hints).unwrap_or_default() + "\n",
+    }
+}
+
+pub(super) fn hint(
+    conf: &expand::ConfigFile,
+    buf: String,
+    max: usize,
+    ranking: &Ranking,
+) -> Vec<(String, String)> {
+    let (_prefix, buf) = clean_buf(buf);
+    let mut compiled = conf.compile(&buf, false);
+    if ranking.fuzzy {
+        // Fuzzy matches needn't start with the buffer, so they need all the
+        // abbreviations
+        for (k, v) in conf.compile_all() {
+            compiled.entry(k).or_insert(v);
+        }
+    }
+    ranking.rank(&buf, compiled).into_iter().take(max).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use expect_test::expect;
 
-    use super::{expand, hint};
+    use super::{Format, Ranking, expand, hint, render};
 
     fn serialize(v: &[(String, String)]) -> String {
         let mut s = String::with_capacity(v.len());
@@ -34,7 +216,7 @@ mod tests {
     #[test]
     fn test_hint_git_commit_space() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
-        let hints = hint(&conf, String::from("git commit "), 5);
+        let hints = hint(&conf, String::from("git commit "), 5, &Ranking::default());
         let expected = expect![[r#"
             git commit -a -> git commit --amend 
             git commit -m -> git commit --message 
@@ -45,21 +227,112 @@ mod tests {
     #[test]
     fn test_hint_git_s() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
-        let hints = hint(&conf, String::from("git s"), 5);
+        let hints = hint(&conf, String::from("git s"), 5, &Ranking::default());
         let expected = expect![[r#"
             git s -> git status 
-            git see -> git send-email 
-            git send-e -> git send-email 
-            git send-p -> git send-pack 
-            git sep -> git send-pack 
+            git sh -> git stash 
+            git si -> git sh-i18n 
+            git sp -> git sparse-checkout 
+            git ss -> git sh-setup 
+        "#]];
+        expected.assert_eq(&serialize(&hints));
+    }
+
+    #[test]
+    fn test_hint_fuzzy() {
+        let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
+        let ranking = Ranking {
+            fuzzy: true,
+            ..Ranking::default()
+        };
+        let hints = hint(&conf, String::from("gsm"), 2, &ranking);
+        let expected = expect![[r#"
+            gsu -> git submodule 
+            gsy -> git symbolic-ref 
         "#]];
         expected.assert_eq(&serialize(&hints));
     }
 
+    #[test]
+    fn test_render() {
+        let conf = toml::from_str::<expand::ConfigFile>(
+            r#"
+            [cmds.git]
+            short = "g"
+            desc = "The stupid content tracker"
+            [cmds.git.subs.status]
+            short = "s"
+            desc = "Show the working tree status"
+            [cmds.git.subs.stash]
+            short = "sh"
+            "#,
+        )
+        .unwrap();
+        let hints = [
+            (String::from("gs"), String::from("git status ")),
+            (String::from("gsh"), String::from("git stash ")),
+        ];
+        let go = |format, width| render(&conf, String::from("echo && gs"), &hints, format, width);
+        let expected = expect![[r#"
+            gs  --> git status  Show the working tree status
+            gsh --> git stash
+        "#]];
+        expected.assert_eq(&go(Format::Text, None));
+        expected.assert_eq(&go(Format::Text, Some(40)));
+        let hints = [
+            (String::from("gsh"), String::from("git stash ")),
+            (String::from("gp"), String::from("git push ")),
+        ];
+        let expected = expect![[r#"
+            gsh --> git stash  gp  --> git push
+        "#]];
+        expected.assert_eq(&render(
+            &conf,
+            String::from("gs"),
+            &hints,
+            Format::Text,
+            Some(40),
+        ));
+        let expected = expect![[r#"
+            0 2 underline
+            8 18 fg=8
+            20 48 fg=8
+            49 51 underline
+            51 52 bold
+            57 66 fg=8
+
+            gs  --> git status  Show the working tree status
+            gsh --> git stash
+        "#]];
+        expected.assert_eq(&go(Format::Zsh, None));
+        let expected = expect![[r#"
+            [{"abbreviation":"gs","expansion":"git status ","description":"Show the working tree status","typed":2},{"abbreviation":"gsh","expansion":"git stash ","description":null,"typed":2}]
+        "#]];
+        expected.assert_eq(&go(Format::Json, None));
+    }
+
+    #[test]
+    fn test_render_bundled() {
+        let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
+        let hints = hint(&conf, String::from("git s"), 3, &Ranking::default());
+        let expected = expect![[r#"
+            git s  --> git status   Show the working tree status
+            git sh --> git stash
+            git si --> git sh-i18n
+        "#]];
+        expected.assert_eq(&render(
+            &conf,
+            String::from("git s"),
+            &hints,
+            Format::Text,
+            None,
+        ));
+    }
+
     #[test]
     fn test_hint_git_shor() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
-        let hints = hint(&conf, String::from("git shor"), 5);
+        let hints = hint(&conf, String::from("git shor"), 5, &Ranking::default());
         let expected = expect![[r#"
             git shor -> git shortlog 
             git short -> git shortlog 
@@ -70,7 +343,12 @@ mod tests {
     #[test]
     fn test_hint_git_commit_m() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
-        let hints = hint(&conf, String::from("git commit -m"), usize::MAX);
+        let hints = hint(
+            &conf,
+            String::from("git commit -m"),
+            usize::MAX,
+            &Ranking::default(),
+        );
         let expected = expect![[r#"
             git commit -m -> git commit --message 
         "#]];
@@ -81,7 +359,7 @@ mod tests {
     fn test_hint_git_submo() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
         assert_eq!(
-            hint(&conf, String::from("git submo"), 5)
+            hint(&conf, String::from("git submo"), 5, &Ranking::default())
                 .iter()
                 .map(|(k, v)| (k.as_str(), v.as_str()))
                 .collect::<Vec<_>>(),
@@ -95,20 +373,25 @@ mod tests {
     #[test]
     fn test_hint_git_submodule() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
-        let hints = hint(&conf, String::from("git submodule"), usize::MAX);
+        let hints = hint(
+            &conf,
+            String::from("git submodule"),
+            usize::MAX,
+            &Ranking::default(),
+        );
         let expected = expect![[r#"
-            git submodule ab -> git submodule absorbgitdirs 
-            git submodule ad -> git submodule add 
             git submodule d -> git submodule deinit 
             git submodule f -> git submodule foreach 
             git submodule i -> git submodule init 
-            git submodule seb -> git submodule set-branch 
-            git submodule seu -> git submodule set-url 
+            git submodule u -> git submodule update 
+            git submodule ab -> git submodule absorbgitdirs 
+            git submodule ad -> git submodule add 
             git submodule st -> git submodule status 
             git submodule su -> git submodule summary 
             git submodule sy -> git submodule sync 
-            git submodule u -> git submodule update 
             git submodule ui -> git submodule update --init 
+            git submodule seb -> git submodule set-branch 
+            git submodule seu -> git submodule set-url 
             git submodule update -i -> git submodule update --init 
         "#]];
         expected.assert_eq(&serialize(&hints));
@@ -117,20 +400,20 @@ mod tests {
     #[test]
     fn test_hint_gsu() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
-        let hints = hint(&conf, String::from("gsu"), usize::MAX);
+        let hints = hint(&conf, String::from("gsu"), usize::MAX, &Ranking::default());
         let expected = expect![[r#"
             gsu -> git submodule 
-            gsuab -> git submodule absorbgitdirs 
-            gsuad -> git submodule add 
             gsud -> git submodule deinit 
             gsuf -> git submodule foreach 
             gsui -> git submodule init 
-            gsuseb -> git submodule set-branch 
-            gsuseu -> git submodule set-url 
+            gsuu -> git submodule update 
+            gsuab -> git submodule absorbgitdirs 
+            gsuad -> git submodule add 
             gsust -> git submodule status 
             gsusu -> git submodule summary 
             gsusy -> git submodule sync 
-            gsuu -> git submodule update 
+            gsuseb -> git submodule set-branch 
+            gsuseu -> git submodule set-url 
         "#]];
         expected.assert_eq(&serialize(&hints));
     }
@@ -138,7 +421,7 @@ mod tests {
     #[test]
     fn test_hint_grb() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
-        let hints = hint(&conf, String::from("grb"), usize::MAX);
+        let hints = hint(&conf, String::from("grb"), usize::MAX, &Ranking::default());
         let expected = expect![[r#"
             grb -> git rebase 
             grba -> git rebase --abort 
@@ -147,7 +430,12 @@ mod tests {
         "#]];
         expected.assert_eq(&serialize(&hints));
 
-        let hints = hint(&conf, String::from("git rb"), usize::MAX);
+        let hints = hint(
+            &conf,
+            String::from("git rb"),
+            usize::MAX,
+            &Ranking::default(),
+        );
         let expected = expect![[r#"
             git rb -> git rebase 
             git rba -> git rebase --abort 
@@ -161,11 +449,23 @@ mod tests {
     fn test_hint_compound() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
         assert_eq!(
-            hint(&conf, String::from("echo foo && git shor"), 5).len(),
+            hint(
+                &conf,
+                String::from("echo foo && git shor"),
+                5,
+                &Ranking::default()
+            )
+            .len(),
             2
         );
         assert_eq!(
-            hint(&conf, String::from("echo foo && git status && git shor"), 5).len(),
+            hint(
+                &conf,
+                String::from("echo foo && git status && git shor"),
+                5,
+                &Ranking::default()
+            )
+            .len(),
             2
         );
     }
@@ -173,14 +473,19 @@ mod tests {
     #[test]
     fn test_hint_flag() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
-        let hints = hint(&conf, String::from("git rebase -"), usize::MAX);
+        let hints = hint(
+            &conf,
+            String::from("git rebase -"),
+            usize::MAX,
+            &Ranking::default(),
+        );
         let expected = expect![[r#"
-            git rebase --a -> git rebase --abort 
-            git rebase --c -> git rebase --continue 
-            git rebase --i -> git rebase --interactive 
             git rebase -a -> git rebase --abort 
             git rebase -c -> git rebase --continue 
             git rebase -i -> git rebase --interactive 
+            git rebase --a -> git rebase --abort 
+            git rebase --c -> git rebase --continue 
+            git rebase --i -> git rebase --interactive 
         "#]];
         expected.assert_eq(&serialize(&hints));
     }
@@ -189,12 +494,19 @@ mod tests {
     fn test_hint_flag_compound() {
         let conf = expand::ConfigFile::from_file("conf/conf.toml").unwrap();
         assert_eq!(
-            hint(&conf, String::from("cargo --verbose b"), 5)
-                .iter()
-                .map(|(k, v)| (k.as_str(), v.as_str()))
-                .collect::<Vec<_>>(),
-            // TODO any
-            []
+            hint(
+                &conf,
+                String::from("cargo --verbose b"),
+                2,
+                &Ranking::default()
+            )
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect::<Vec<_>>(),
+            [
+                ("cargo --verbose b", "cargo --verbose build "),
+                ("cargo --verbose be", "cargo --verbose bench ")
+            ]
         );
     }
 }
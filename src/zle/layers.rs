@@ -0,0 +1,418 @@
+//! Configs are layered, from lowest to highest precedence:
+//!
+//! - `/etc/zbr/config.toml`
+//! - the config passed on the command line, see [`locate`]
+//! - `$XDG_CONFIG_HOME/zbr/overrides.toml`
+//! - `.zbr.toml` in the current directory and its ancestors, outermost first
+//! - `.zbr.local.toml` next to each `.zbr.toml`, for untracked changes
+//!
+//! Higher layers can override shorts, disable commands, and add commands.
+//! Project layers, which come with the code in a directory, can only run
+//! commands for `values` and include other configs once the directory is
+//! trusted, see [`trust`].
+
+use std::io::{self, Write as _};
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use tracing::warn;
+
+use super::dirs;
+use super::expand::{ConfigFile, ConfigFileError, Policy};
+use super::extract::{Cmd, Cmds};
+use super::values::Values;
+
+const SYSTEM: &str = "/etc/zbr/config.toml";
+const PROJECT: &str = ".zbr.toml";
+const LOCAL: &str = ".zbr.local.toml";
+
+/// Generated from the tools in `conf/manifest.toml`
+pub(super) const BUNDLED: &str = include_str!("../../conf/conf.toml");
+pub(super) const BUNDLED_PATH: &str = "<bundled>";
+
+/// The config passed on the command line, or else `$ZBR_CONF`, or else
+/// `config.toml` in `$XDG_CONFIG_HOME/zbr` or `~/.config/zbr`
+pub(super) fn locate(conf: Option<PathBuf>) -> Option<PathBuf> {
+    let var = |v: &str| env::var_os(v).filter(|v| !v.is_empty()).map(PathBuf::from);
+    conf.or_else(|| var("ZBR_CONF")).or_else(|| {
+        let xdg = var("XDG_CONFIG_HOME").map(|d| d.join("zbr"));
+        let home = var("HOME").map(|h| h.join(".config").join("zbr"));
+        xdg.into_iter()
+            .chain(home)
+            .map(|d| d.join("config.toml"))
+            .find(|p| p.is_file())
+    })
+}
+
+/// The config found by [`locate`], or else the bundled default
+pub(super) fn base(conf: Option<PathBuf>) -> Result<ConfigFile, ConfigFileError> {
+    match locate(conf) {
+        Some(path) => ConfigFile::from_file(path),
+        None => toml::from_str(BUNDLED)
+            .map_err(|e| ConfigFileError::Toml(e, PathBuf::from(BUNDLED_PATH))),
+    }
+}
+
+/// The layers above the config passed on the command line that apply in
+/// `dir`, in order of precedence
+fn paths(dir: &Path) -> Vec<PathBuf> {
+    let mut r = Vec::from_iter(dirs::config_dir().map(|d| d.join("overrides.toml")));
+    let mut ancestors = dir.ancestors().collect::<Vec<_>>();
+    ancestors.reverse();
+    for a in ancestors {
+        r.push(a.join(PROJECT));
+        r.push(a.join(LOCAL));
+    }
+    r
+}
+
+fn merge_cmd(base: &mut Cmd, over: Cmd) {
+    if !over.short.is_empty() {
+        base.short = over.short;
+    }
+    if over.desc.is_some() {
+        base.desc = over.desc;
+    }
+    base.flags.extend(over.flags);
+    if over.no_args.is_some() {
+        base.no_args = over.no_args;
+    }
+    merge_cmds(&mut base.subs, over.subs);
+    if over.values.is_some() {
+        base.values = over.values;
+    }
+    if over.disable.is_some() {
+        base.disable = over.disable;
+    }
+}
+
+fn merge_cmds(base: &mut Cmds, over: Cmds) {
+    for (long, cmd) in over.0 {
+        match base.0.get_mut(&long) {
+            Some(b) => merge_cmd(b, cmd),
+            None => {
+                base.0.insert(long, cmd);
+            }
+        }
+    }
+}
+
+/// [`SYSTEM`], the [`paths`] in `dir`, and the [`trusted_path`], whether or
+/// not they exist
+pub(super) fn candidates(dir: &Path) -> Vec<PathBuf> {
+    let mut r = vec![PathBuf::from(SYSTEM)];
+    r.extend(paths(dir));
+    r.extend(trusted_path());
+    r
+}
+
+/// The directories whose project layers are trusted, one per line
+fn trusted_path() -> Option<PathBuf> {
+    dirs::state_dir().map(|d| d.join("trusted"))
+}
+
+fn trusted() -> Vec<PathBuf> {
+    trusted_path()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .map(|s| s.lines().map(PathBuf::from).collect())
+        .unwrap_or_default()
+}
+
+/// Let the project layers in `dir` run commands and include other configs
+pub(super) fn trust(dir: &Path) -> io::Result<()> {
+    let dir = dir.canonicalize()?;
+    let path = trusted_path().ok_or_else(|| io::Error::other("no home directory"))?;
+    if trusted().contains(&dir) {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", dir.display())
+}
+
+/// Whether `layer` is a [`PROJECT`] or [`LOCAL`] layer outside the `trusted`
+/// directories
+fn is_untrusted(layer: &Path, trusted: &[PathBuf]) -> bool {
+    let is_project = layer
+        .file_name()
+        .is_some_and(|n| n == PROJECT || n == LOCAL);
+    is_project
+        && !layer
+            .parent()
+            .and_then(|d| d.canonicalize().ok())
+            .is_some_and(|d| trusted.contains(&d))
+}
+
+/// Remove `values` printed by commands, and report whether there were any
+fn strip_commands(cmds: &mut Cmds) -> bool {
+    fn strip(values: &mut Option<Values>) -> bool {
+        let is_command = matches!(values, Some(Values::Command { .. }));
+        if is_command {
+            *values = None;
+        }
+        is_command
+    }
+    let mut stripped = false;
+    for cmd in cmds.0.values_mut() {
+        stripped |= strip(&mut cmd.values);
+        for flag in cmd.flags.values_mut() {
+            stripped |= strip(&mut flag.values);
+        }
+        stripped |= strip_commands(&mut cmd.subs);
+    }
+    stripped
+}
+
+/// Read a layer that isn't trusted, ignoring its includes and commands
+fn read_untrusted(layer: &Path) -> Result<ConfigFile, ConfigFileError> {
+    let mut conf = ConfigFile::read(layer)?;
+    let mut ignored = Vec::new();
+    if !conf.include.is_empty() {
+        conf.include.clear();
+        ignored.push("`include`");
+    }
+    if strip_commands(&mut conf.cmds) {
+        ignored.push("`values.command`");
+    }
+    if !ignored.is_empty() {
+        warn!(
+            "Ignoring {} in {}, see `zbr trust`",
+            ignored.join(" and "),
+            layer.display()
+        );
+    }
+    Ok(conf)
+}
+
+/// Reject commands that `over` adds to `base` without a short. `parent` is the
+/// name of the command that they are subcommands of, if any.
+fn check_added(
+    base: &Cmds,
+    over: &Cmds,
+    parent: &str,
+    layer: &Path,
+) -> Result<(), ConfigFileError> {
+    for (long, cmd) in &over.0 {
+        let name = if parent.is_empty() {
+            long.clone()
+        } else {
+            format!("{parent} {long}")
+        };
+        match base.0.get(long) {
+            Some(b) => check_added(&b.subs, &cmd.subs, &name, layer)?,
+            None if cmd.disable == Some(true) => (),
+            None if cmd.short.is_empty() => {
+                return Err(ConfigFileError::MissingShort(name, layer.to_path_buf()));
+            }
+            None => check_added(&Cmds::default(), &cmd.subs, &name, layer)?,
+        }
+    }
+    Ok(())
+}
+
+/// Add `over` to `base`, with `over` taking precedence
+pub(super) fn merge(base: &mut ConfigFile, over: ConfigFile) {
+    merge_cmds(&mut base.cmds, over.cmds);
+    if over.shadowed.default != Policy::default() {
+        base.shadowed.default = over.shadowed.default;
+    }
+    base.shadowed.keys.extend(over.shadowed.keys);
+    base.disable.extend(over.disable);
+    base.overrides.extend(over.overrides);
+    base.sources.extend(over.sources);
+}
+
+/// Remove disabled commands
+fn prune(cmds: &mut Cmds) {
+    cmds.0.retain(|_, c| c.disable != Some(true));
+    for cmd in cmds.0.values_mut() {
+        prune(&mut cmd.subs);
+    }
+}
+
+/// Layer `base` between `system` and `layers`. Missing layers are skipped,
+/// and project layers outside the `trusted` directories are read without their
+/// includes and commands.
+fn load_in(
+    system: Option<&Path>,
+    base: ConfigFile,
+    layers: &[PathBuf],
+    trusted: &[PathBuf],
+) -> Result<ConfigFile, ConfigFileError> {
+    let mut conf = match system.filter(|s| s.is_file()) {
+        Some(s) => ConfigFile::from_file(s)?,
+        None => ConfigFile::default(),
+    };
+    merge(&mut conf, base);
+    for layer in layers.iter().filter(|l| l.is_file()) {
+        let over = if is_untrusted(layer, trusted) {
+            read_untrusted(layer)?
+        } else {
+            ConfigFile::from_file(layer)?
+        };
+        check_added(&conf.cmds, &over.cmds, "", layer)?;
+        merge(&mut conf, over);
+    }
+    prune(&mut conf.cmds);
+    Ok(conf)
+}
+
+/// Load the [`base`] config with the other configs that apply in the current
+/// directory
+pub(super) fn load(conf: Option<PathBuf>) -> Result<ConfigFile, ConfigFileError> {
+    let layers = env::current_dir().map(|d| paths(&d)).unwrap_or_default();
+    load_in(Some(Path::new(SYSTEM)), base(conf)?, &layers, &trusted())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{BUNDLED, load_in, paths};
+    use crate::zle::compile::compile_with_conflicts;
+    use crate::zle::dirs::TempDir;
+    use crate::zle::expand::{ConfigFile, expand};
+    use crate::zle::values::Values;
+
+    #[test]
+    fn test_paths() {
+        let ps = paths(Path::new("/a/b"));
+        assert_eq!(
+            ps[ps.len() - 4..],
+            [
+                Path::new("/a/.zbr.toml"),
+                Path::new("/a/.zbr.local.toml"),
+                Path::new("/a/b/.zbr.toml"),
+                Path::new("/a/b/.zbr.local.toml")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bundled() {
+        let go = |l: &str| {
+            let conf = toml::from_str::<ConfigFile>(BUNDLED).unwrap();
+            expand(conf, String::from(l), String::new(), Some(false))
+        };
+        assert_eq!(go("gs").as_deref(), Some("git status "));
+        assert_eq!(
+            go("git -C ../other co").as_deref(),
+            Some("git -C ../other checkout ")
+        );
+        // The widget doesn't report conflicts, so the default has none
+        let conf = toml::from_str::<ConfigFile>(BUNDLED).unwrap();
+        assert_eq!(compile_with_conflicts(&conf.cmds).1, []);
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = TempDir::new("layers");
+        let write = |name: &str, content: &str| dir.write(name, content);
+        let base = write(
+            "base.toml",
+            r#"
+            [cmds.git]
+            short = "g"
+            [cmds.git.subs.status]
+            short = "s"
+            [cmds.git.subs.stash]
+            short = "sh"
+            "#,
+        );
+        let project = write(
+            ".zbr.toml",
+            r#"
+            [cmds.git.subs.status]
+            short = "st"
+            [cmds.git.subs.stash]
+            disable = true
+            [cmds.just]
+            short = "j"
+            "#,
+        );
+        let local = write(
+            ".zbr.local.toml",
+            r#"
+            [cmds.git.subs.status]
+            short = "ss"
+            "#,
+        );
+        let base = ConfigFile::from_file(base).unwrap();
+        let conf = load_in(None, base, &[project, local], &[]).unwrap();
+        let go = |l: &str| {
+            let conf = toml::to_string(&conf).unwrap();
+            expand(
+                toml::from_str(&conf).unwrap(),
+                String::from(l),
+                String::new(),
+                Some(false),
+            )
+        };
+        assert_eq!(go("gss").as_deref(), Some("git status "));
+        assert_eq!(go("gst").as_deref(), None);
+        assert_eq!(go("gsh").as_deref(), None);
+        assert_eq!(go("j").as_deref(), Some("just "));
+    }
+    #[test]
+    fn test_reenable() {
+        let dir = TempDir::new("reenable");
+        let write = |name: &str, content: &str| dir.write(name, content);
+        let base = toml::from_str(
+            r#"
+            [cmds.git]
+            short = "g"
+            [cmds.git.subs.stash]
+            short = "sh"
+            "#,
+        )
+        .unwrap();
+        let disable = write("disable.toml", "[cmds.git.subs.stash]\ndisable = true");
+        let enable = write("enable.toml", "[cmds.git.subs.stash]\ndisable = false");
+        let rename = write("rename.toml", "[cmds.git]\nshort = \"gg\"");
+        let conf = load_in(None, base, &[disable, enable, rename], &[]).unwrap();
+        assert!(conf.cmds.0["git"].subs.0.contains_key("stash"));
+        assert_eq!(conf.cmds.0["git"].subs.0["stash"].disable, Some(false));
+
+        let add = write("add.toml", "[cmds.just.subs.build]\nshort = \"b\"");
+        let err = load_in(None, ConfigFile::default(), &[add], &[]).unwrap_err();
+        assert!(err.to_string().contains("adds `just` without a `short`"));
+    }
+
+    #[test]
+    fn test_trust() {
+        let dir = TempDir::new("trust");
+        dir.write("just.toml", "[cmds.just]\nshort = \"j\"");
+        let project = dir.write(
+            ".zbr.toml",
+            r#"
+            include = ["just.toml"]
+            [cmds.git]
+            short = "g"
+            values = { command = "touch pwned" }
+            "#,
+        );
+        let load = |trusted: &[_]| {
+            load_in(
+                None,
+                ConfigFile::default(),
+                std::slice::from_ref(&project),
+                trusted,
+            )
+            .unwrap()
+        };
+        let conf = load(&[]);
+        assert_eq!(conf.cmds.0["git"].values, None);
+        assert!(!conf.cmds.0.contains_key("just"));
+        let conf = load(&[dir.canonicalize().unwrap()]);
+        assert!(matches!(
+            conf.cmds.0["git"].values,
+            Some(Values::Command { .. })
+        ));
+        assert!(conf.cmds.0.contains_key("just"));
+    }
+}
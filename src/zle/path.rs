@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+fn is_executable(p: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    p.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(p: &Path) -> bool {
+    p.is_file()
+}
+
+/// Find an executable named `name` on `$PATH`
+pub(super) fn find_executable(name: &str) -> Option<PathBuf> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|p| is_executable(p))
+}
+
+/// The names of all executables on `$PATH`
+pub(super) fn executables() -> HashSet<String> {
+    let mut names = HashSet::new();
+    let Some(path) = env::var_os("PATH") else {
+        return names;
+    };
+    for dir in env::split_paths(&path) {
+        let Ok(entries) = dir.read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if is_executable(&entry.path())
+                && let Ok(name) = entry.file_name().into_string()
+            {
+                names.insert(name);
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{executables, find_executable};
+
+    #[test]
+    fn test_find_executable() {
+        assert!(find_executable("sh").is_some());
+        assert!(find_executable("zbr-nonexistent-executable").is_none());
+        assert!(executables().contains("sh"));
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::dirs::{self, Cache};
+use super::extract::{Cmd, Cmds, Flag};
+use super::values::Values;
+
+/// Names derived from the project that contains the current directory
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(super) struct Project {
+    /// Members of the Cargo workspace, for `cargo build -p`
+    cargo: Vec<String>,
+    /// Scripts in `package.json`, for `npm run`
+    npm: Vec<String>,
+    /// Targets in the `Makefile`, for `make`
+    make: Vec<String>,
+    /// Recipes in the `justfile`, for `just`
+    just: Vec<String>,
+}
+
+/// The closest `name` in `dir` or its ancestors
+fn find(dir: &Path, names: &[&str]) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|a| names.iter().map(move |n| a.join(n)))
+        .find(|p| p.is_file())
+}
+
+/// The outermost `Cargo.toml` with a `[workspace]`, or else the closest one.
+/// Cached in `cache_dir`, if given, by the modification times of the
+/// manifests in `dir` and its ancestors.
+fn cargo_manifest(dir: &Path, cache_dir: Option<&Path>) -> Option<PathBuf> {
+    let closest = find(dir, &["Cargo.toml"])?;
+    let manifests = closest
+        .parent()?
+        .ancestors()
+        .map(|a| a.join("Cargo.toml"))
+        .filter(|p| p.is_file())
+        .collect::<Vec<_>>();
+    let key = manifests.iter().map(|m| dirs::stamp(m)).collect::<Vec<_>>();
+    let cache = cache_dir.map(|d| Cache::new(d, "cargo", &closest, &key.join("\t")));
+    if let Some(workspace) = cache.as_ref().and_then(|c| c.read(None)) {
+        return Some(PathBuf::from(workspace));
+    }
+    let workspace = manifests
+        .into_iter()
+        .rev()
+        .find(|p| {
+            fs::read_to_string(p)
+                .ok()
+                .and_then(|s| toml::from_str::<toml::Table>(&s).ok())
+                .is_some_and(|t| t.contains_key("workspace"))
+        })
+        .unwrap_or(closest);
+    if let Some(cache) = cache {
+        cache.write(&workspace.to_string_lossy());
+    }
+    Some(workspace)
+}
+
+fn package_name(manifest: &Path) -> Option<String> {
+    let table = toml::from_str::<toml::Table>(&fs::read_to_string(manifest).ok()?).ok()?;
+    let name = table.get("package")?.get("name")?.as_str()?;
+    Some(String::from(name))
+}
+
+/// The packages in a Cargo workspace. Members may end in `/*`.
+fn cargo_members(manifest: &Path) -> Vec<String> {
+    let Some(root) = manifest.parent() else {
+        return Vec::new();
+    };
+    let mut names = Vec::from_iter(package_name(manifest));
+    let Some(table) = fs::read_to_string(manifest)
+        .ok()
+        .and_then(|s| toml::from_str::<toml::Table>(&s).ok())
+    else {
+        return names;
+    };
+    let members = table
+        .get("workspace")
+        .and_then(|w| w.get("members"))
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|m| m.as_str());
+    for member in members {
+        let dirs = match member.strip_suffix("/*") {
+            Some(parent) => fs::read_dir(root.join(parent))
+                .into_iter()
+                .flatten()
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .collect(),
+            None => vec![root.join(member)],
+        };
+        for dir in dirs {
+            names.extend(package_name(&dir.join("Cargo.toml")));
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn npm_scripts(manifest: &Path) -> Vec<String> {
+    #[derive(serde::Deserialize)]
+    struct Package {
+        #[serde(default)]
+        scripts: BTreeMap<String, serde_json::Value>,
+    }
+    fs::read_to_string(manifest)
+        .ok()
+        .and_then(|s| serde_json::from_str::<Package>(&s).ok())
+        .map(|p| p.scripts.into_keys().collect())
+        .unwrap_or_default()
+}
+
+/// Explicit targets, e.g., `build` in `build: main.o`, but not pattern rules,
+/// special targets like `.PHONY`, or variables like `CC := gcc` and
+/// `CC ::= gcc`
+fn make_targets(text: &str) -> Vec<String> {
+    let mut targets = Vec::new();
+    for line in text.lines() {
+        if line.starts_with(['\t', ' ', '#', '.']) {
+            continue;
+        }
+        let Some((names, rest)) = line.split_once(':') else {
+            continue;
+        };
+        // `:=`, `::=`, and `:::=` assign variables
+        let assigns = rest.trim_start_matches(':').starts_with('=');
+        if assigns || names.contains(['=', '%', '$']) {
+            continue;
+        }
+        targets.extend(names.split_whitespace().map(String::from));
+    }
+    targets.sort();
+    targets.dedup();
+    targets
+}
+
+/// Public recipes, e.g., `test` in `test *args: build`
+fn just_recipes(text: &str) -> Vec<String> {
+    let mut recipes = Vec::new();
+    for line in text.lines() {
+        if line.starts_with(['\t', ' ', '#', '_', '[']) {
+            continue;
+        }
+        let Some((head, rest)) = line.split_once(':') else {
+            continue;
+        };
+        if rest.starts_with('=') {
+            continue;
+        }
+        let mut words = head.split_whitespace();
+        let Some(name) = words.next().map(|n| n.trim_start_matches('@')) else {
+            continue;
+        };
+        let keyword = ["alias", "export", "import", "mod", "set"].contains(&name);
+        if keyword || name.is_empty() || name.starts_with('_') {
+            continue;
+        }
+        recipes.push(String::from(name));
+    }
+    recipes.sort();
+    recipes.dedup();
+    recipes
+}
+
+/// Cached in `cache_dir`, if given, by the modification time of the manifest
+fn cached(
+    cache_dir: Option<&Path>,
+    manifest: &Path,
+    parse: impl FnOnce(&Path) -> Vec<String>,
+) -> Vec<String> {
+    let Some(dir) = cache_dir else {
+        return parse(manifest);
+    };
+    let cache = Cache::new(dir, "project", manifest, &dirs::stamp(manifest));
+    if let Some(names) = cache.read(None) {
+        return names.lines().map(String::from).collect();
+    }
+    let names = parse(manifest);
+    cache.write(&names.join("\n"));
+    names
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).unwrap_or_default()
+}
+
+impl Project {
+    /// The names in the project that contains `dir`, cached in `cache_dir`, if
+    /// given
+    pub(super) fn detect(dir: &Path, cache_dir: Option<&Path>) -> Self {
+        let cached = |m: PathBuf, parse: fn(&Path) -> Vec<String>| cached(cache_dir, &m, parse);
+        let cargo = cargo_manifest(dir, cache_dir).map(|m| cached(m, cargo_members));
+        let npm = find(dir, &["package.json"]).map(|m| cached(m, npm_scripts));
+        let make = find(dir, &["GNUmakefile", "makefile", "Makefile"])
+            .map(|m| cached(m, |m| make_targets(&read(m))));
+        let just = find(dir, &["justfile", ".justfile", "Justfile"])
+            .map(|m| cached(m, |m| just_recipes(&read(m))));
+        Self {
+            cargo: cargo.unwrap_or_default(),
+            npm: npm.unwrap_or_default(),
+            make: make.unwrap_or_default(),
+            just: just.unwrap_or_default(),
+        }
+    }
+
+    /// Add the names as [`Values`] of the commands, e.g., `make` and
+    /// `npm run`. Commands that aren't configured yet are added.
+    pub(super) fn merge(self, cmds: &mut Cmds) {
+        fn cmd<'a>(cmds: &'a mut Cmds, long: &str) -> &'a mut Cmd {
+            cmds.0.entry(String::from(long)).or_insert_with(|| Cmd {
+                short: String::from(long),
+                desc: None,
+                flags: HashMap::new(),
+                no_args: None,
+                subs: Cmds::default(),
+                values: None,
+                disable: None,
+            })
+        }
+        fn add(values: &mut Option<Values>, names: Vec<String>) {
+            match values {
+                None => *values = Some(Values::List(names)),
+                Some(Values::List(vs)) => {
+                    vs.extend(names);
+                    vs.sort();
+                    vs.dedup();
+                }
+                // Configured explicitly
+                Some(Values::Command { .. }) => (),
+            }
+        }
+
+        if !self.cargo.is_empty() {
+            let build = cmd(&mut cmd(cmds, "cargo").subs, "build");
+            let package = build
+                .flags
+                .entry(String::from("--package"))
+                .or_insert_with(|| Flag {
+                    short: String::from("p"),
+                    arity: 1,
+                    alias: Some(String::from("-p")),
+                    ..Flag::default()
+                });
+            add(&mut package.values, self.cargo);
+        }
+        if !self.npm.is_empty() {
+            add(&mut cmd(&mut cmd(cmds, "npm").subs, "run").values, self.npm);
+        }
+        if !self.make.is_empty() {
+            add(&mut cmd(cmds, "make").values, self.make);
+        }
+        if !self.just.is_empty() {
+            add(&mut cmd(cmds, "just").values, self.just);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{Project, cargo_manifest, cargo_members, just_recipes, make_targets};
+    use crate::zle::dirs::TempDir;
+    use crate::zle::expand::{ConfigFile, expand};
+
+    #[test]
+    fn test_cargo_members() {
+        let root = TempDir::new("cargo");
+        for (path, content) in [
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"app\", \"crates/*\"]\n",
+            ),
+            ("app/Cargo.toml", "[package]\nname = \"app\"\n"),
+            ("crates/zbr-a/Cargo.toml", "[package]\nname = \"zbr-a\"\n"),
+            ("crates/zbr-b/Cargo.toml", "[package]\nname = \"zbr-b\"\n"),
+        ] {
+            root.write(path, content);
+        }
+        let cache = root.join("cache");
+        let manifest = cargo_manifest(&root.join("crates/zbr-a"), Some(&cache)).unwrap();
+        assert_eq!(manifest, root.join("Cargo.toml"));
+        assert_eq!(cargo_members(&manifest), ["app", "zbr-a", "zbr-b"]);
+        // From the cache
+        assert_eq!(
+            cargo_manifest(&root.join("crates/zbr-a"), Some(&cache)),
+            Some(manifest)
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut conf = ConfigFile::default();
+        let project = Project {
+            cargo: vec![String::from("zbr"), String::from("zbr-macros")],
+            npm: vec![String::from("lint"), String::from("test")],
+            make: vec![String::from("all"), String::from("install")],
+            just: vec![String::from("release")],
+        };
+        project.merge(&mut conf.cmds);
+        let conf = toml::to_string(&conf).unwrap();
+        let go = |l: &str| {
+            let conf = toml::from_str(&conf).unwrap();
+            expand(conf, String::from(l), String::new(), Some(false))
+        };
+        assert_eq!(go("make i").as_deref(), Some("make install "));
+        assert_eq!(go("npm run l").as_deref(), Some("npm run lint "));
+        assert_eq!(go("just r").as_deref(), Some("just release "));
+        assert_eq!(
+            go("cargo build -p zbr-").as_deref(),
+            Some("cargo build -p zbr-macros ")
+        );
+    }
+
+    #[test]
+    fn test_make_targets() {
+        let makefile = "\
+CC := gcc
+LD ::= ld
+AR :::= ar
+.PHONY: all test
+all: build
+build test: main.o
+\t$(CC) -o main main.o
+%.o: %.c
+install:: all
+";
+        assert_eq!(make_targets(makefile), ["all", "build", "install", "test"]);
+    }
+
+    #[test]
+    fn test_just_recipes() {
+        let justfile = "\
+set shell := [\"bash\", \"-c\"]
+alias b := build
+version := \"1.0\"
+
+# Build it
+build:
+    cargo build
+
+@test *args: build
+    cargo test {{args}}
+
+_private:
+    echo hi
+";
+        assert_eq!(just_recipes(justfile), ["build", "test"]);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use tracing::debug;
+
+use super::dirs::Cache;
+
+/// Commands with more words than this aren't counted
+const MAX_WORDS: usize = 3;
+
+/// How many bytes before the end of the counted part of the history are
+/// hashed, to tell whether the history was only appended to since
+const TAIL: u64 = 64;
+
+/// How often each command was run, e.g., `git status`, counting the first
+/// [`MAX_WORDS`] words of each command in the history
+#[derive(Debug, Default)]
+pub(super) struct Popularity(HashMap<String, u32>);
+
+/// Zsh writes lines like `: 1700000000:0;git status` with `EXTENDED_HISTORY`.
+fn strip_timestamp(line: &str) -> &str {
+    match line.strip_prefix(": ").and_then(|l| l.split_once(';')) {
+        Some((_, cmd)) => cmd,
+        None => line,
+    }
+}
+
+fn count(history: &str) -> HashMap<String, u32> {
+    let mut counts = HashMap::<String, u32>::new();
+    let cmds = history
+        .lines()
+        .map(strip_timestamp)
+        .flat_map(|l| l.split(['|', '&', ';']));
+    for cmd in cmds {
+        let words = cmd.split_whitespace().take(MAX_WORDS).collect::<Vec<_>>();
+        for n in 1..=words.len() {
+            *counts.entry(words[..n].join(" ")).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// A hash of the [`TAIL`] bytes of `file` before `end`
+fn tail_hash(file: &mut File, end: u64) -> io::Result<u64> {
+    let start = end.saturating_sub(TAIL);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.by_ref().take(end - start).read_to_end(&mut tail)?;
+    let mut hasher = DefaultHasher::new();
+    tail.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// The counts in a cache whose first line is the offset where they end in the
+/// history and the [`tail_hash`] there, if the history still starts with the
+/// part that was counted
+fn read_counts(cache: &str, file: &mut File, len: u64) -> Option<(HashMap<String, u32>, u64)> {
+    let (header, rest) = cache.split_once('\n').unwrap_or((cache, ""));
+    let (offset, hash) = header.split_once('\t')?;
+    let offset = offset.parse::<u64>().ok()?;
+    if offset > len || tail_hash(file, offset).ok()? != hash.parse::<u64>().ok()? {
+        return None;
+    }
+    let counts = rest
+        .lines()
+        .filter_map(|l| l.split_once('\t'))
+        .filter_map(|(n, cmd)| Some((String::from(cmd), n.parse().ok()?)));
+    Some((counts.collect(), offset))
+}
+
+impl Popularity {
+    /// Count the commands in a history file, see [`Popularity::from_file`]
+    pub(super) fn from_history(path: &Path, cache_dir: Option<&Path>) -> Self {
+        Self::from_file(path, cache_dir, "history", |history| Self(count(history)))
+    }
+
+    /// Count the commands in a file with `count`. The counts are cached in the
+    /// `kind` cache in `cache_dir`, if given, and when lines are appended to
+    /// the file, only those are counted.
+    pub(super) fn from_file(
+        path: &Path,
+        cache_dir: Option<&Path>,
+        kind: &str,
+        count: impl FnOnce(&str) -> Self,
+    ) -> Self {
+        let Ok(mut file) = File::open(path) else {
+            return Self::default();
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or_default();
+        let cache = cache_dir.map(|d| Cache::new(d, kind, path, &path.to_string_lossy()));
+        let (mut counts, offset) = cache
+            .as_ref()
+            .and_then(|c| c.read(None))
+            .and_then(|c| read_counts(&c, &mut file, len))
+            .unwrap_or_default();
+        if offset == len {
+            return Self(counts);
+        }
+        let mut new = Vec::new();
+        if let Err(e) = file
+            .seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_to_end(&mut new))
+        {
+            debug!("Couldn't read {}: {e}", path.display());
+            return Self(counts);
+        }
+        // Only whole lines, in case the file is still being written
+        let end = new.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+        // zsh history isn't necessarily UTF-8
+        for (cmd, n) in count(&String::from_utf8_lossy(&new[..end])).0 {
+            *counts.entry(cmd).or_default() += n;
+        }
+        let offset = offset + end as u64;
+        if let Some(cache) = cache
+            && let Ok(hash) = tail_hash(&mut file, offset)
+        {
+            let mut lines = vec![format!("{offset}\t{hash}")];
+            lines.extend(counts.iter().map(|(cmd, n)| format!("{n}\t{cmd}")));
+            cache.write(&lines.join("\n"));
+        }
+        Self(counts)
+    }
+
+    /// Count commands, one per line
+    pub(super) fn from_commands(cmds: &str) -> Self {
+        Self(count(cmds))
+    }
+
+    /// Add the counts in `other`
+    pub(super) fn add(&mut self, other: Self) {
+        for (cmd, n) in other.0 {
+            *self.0.entry(cmd).or_default() += n;
+        }
+    }
+
+    fn get(&self, expansion: &str) -> u32 {
+        self.0.get(expansion.trim_end()).copied().unwrap_or(0)
+    }
+}
+
+/// How to order hints
+#[derive(Debug, Default)]
+pub(super) struct Ranking {
+    pub(super) popularity: Popularity,
+    /// Also match abbreviations whose expansions contain the buffer as a
+    /// subsequence, e.g., `gsm` for `git submodule`
+    pub(super) fuzzy: bool,
+}
+
+/// Whether the characters of `needle` appear in `haystack` in order
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut chars = haystack.chars();
+    needle.chars().all(|c| chars.any(|h| h == c))
+}
+
+impl Ranking {
+    /// Higher is better. Abbreviations that are exactly the buffer, or whose
+    /// expansions contain the last word of the buffer, come first, and fuzzy
+    /// matches come last. Each doubling of popularity is worth as much as 4
+    /// fewer characters in the abbreviation.
+    fn score(&self, buf: &str, key: &str, expansion: &str) -> i64 {
+        let last = buf.split_whitespace().last();
+        let exact = key == buf || expansion.split_whitespace().any(|w| Some(w) == last);
+        let fuzzy = !key.starts_with(buf);
+        let popularity = self.popularity.get(expansion).saturating_add(1).ilog2();
+        let len = i64::try_from(key.len()).unwrap_or(i64::MAX);
+        i64::from(exact) * 1000 - i64::from(fuzzy) * 1000 + i64::from(popularity) * 4 - len
+    }
+
+    /// The abbreviations in `compiled` that match `buf`, best first. In fuzzy
+    /// mode, only the best abbreviation for each expansion is kept.
+    pub(super) fn rank(
+        &self,
+        buf: &str,
+        compiled: BTreeMap<String, String>,
+    ) -> Vec<(String, String)> {
+        let mut matches = compiled
+            .into_iter()
+            .filter(|(k, v)| k.starts_with(buf) || (self.fuzzy && is_subsequence(buf, v)))
+            .map(|(k, v)| (-self.score(buf, &k, &v), k, v))
+            .collect::<Vec<_>>();
+        matches.sort();
+        if self.fuzzy {
+            let mut seen = HashSet::new();
+            matches.retain(|(_, _, v)| seen.insert(v.clone()));
+        }
+        matches.into_iter().map(|(_, k, v)| (k, v)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::fs;
+    use std::io::Write as _;
+
+    use super::{Popularity, Ranking, count, is_subsequence};
+    use crate::zle::dirs::TempDir;
+
+    #[test]
+    fn test_count() {
+        let counts = count(": 1700000000:0;git status\ngit stash pop -q && git status\nls\n");
+        assert_eq!(counts["git status"], 2);
+        assert_eq!(counts["git"], 3);
+        assert_eq!(counts["git stash pop"], 1);
+        assert!(!counts.contains_key("git stash pop -q"));
+    }
+
+    #[test]
+    fn test_from_history() {
+        let dir = TempDir::new("history");
+        let path = dir.join("history");
+        let append = |s: &str| {
+            let mut file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.write_all(s.as_bytes()).unwrap();
+        };
+        let counts = || Popularity::from_history(&path, Some(&dir.join("cache"))).0;
+        append("git status\ngit status\n");
+        assert_eq!(counts()["git status"], 2);
+        append("git status\ngit pu");
+        assert_eq!(counts()["git status"], 3);
+        assert!(!counts().contains_key("git pu"));
+        append("sh\n");
+        assert_eq!(counts()["git push"], 1);
+        // zsh rewrites the history when it gets too long
+        fs::write(&path, "git push\n").unwrap();
+        assert_eq!(counts().get("git status"), None);
+        assert_eq!(counts()["git push"], 1);
+    }
+
+    #[test]
+    fn test_is_subsequence() {
+        assert!(is_subsequence("gsm", "git submodule "));
+        assert!(!is_subsequence("gsm", "git status "));
+    }
+
+    #[test]
+    fn test_rank() {
+        let compiled = BTreeMap::from_iter(
+            [
+                ("gs", "git status "),
+                ("gsee", "git send-email "),
+                ("gsh", "git stash "),
+                ("gsu", "git submodule "),
+                ("git submodule", "git submodule "),
+            ]
+            .map(|(k, v)| (String::from(k), String::from(v))),
+        );
+        let keys = |ranking: &Ranking, buf: &str| {
+            let ranked = ranking.rank(buf, compiled.clone());
+            ranked.into_iter().map(|(k, _)| k).collect::<Vec<_>>()
+        };
+        let mut ranking = Ranking::default();
+        assert_eq!(keys(&ranking, "gs"), ["gs", "gsh", "gsu", "gsee"]);
+        assert_eq!(keys(&ranking, "gsm"), Vec::<String>::new());
+        ranking.fuzzy = true;
+        assert_eq!(keys(&ranking, "gsm"), ["gsu", "gsee"]);
+        ranking.popularity = Popularity(HashMap::from([(String::from("git send-email"), 7)]));
+        assert_eq!(keys(&ranking, "gs"), ["gs", "gsee", "gsh", "gsu"]);
+    }
+}
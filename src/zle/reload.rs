@@ -0,0 +1,335 @@
+//! The merged config is cached along with the modification times of the files
+//! it was read from, and of the layers that don't exist yet. Each run reuses
+//! it until one of those files changes. If the changed config doesn't load,
+//! the last good one is used instead, and the error is printed once.
+//!
+//! Caches are keyed on the base config and the layers that exist, so
+//! directories with the same layers share one. Each has a compiled table of
+//! every abbreviation next to it, which is only read when it's needed, see
+//! [`ConfigFile::compile_all`].
+
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use std::{fs, io};
+
+use tracing::{debug, info, warn};
+
+use super::expand::{ConfigFile, ConfigFileError};
+use super::{check, dirs, layers};
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+struct Cached {
+    /// Modification times, `None` for files that didn't exist
+    files: BTreeMap<PathBuf, Option<SystemTime>>,
+    conf: ConfigFile,
+    /// The last error from loading a newer config
+    #[serde(default)]
+    error: Option<String>,
+}
+
+fn mtime(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn stamp(files: impl IntoIterator<Item = PathBuf>) -> BTreeMap<PathBuf, Option<SystemTime>> {
+    files.into_iter().map(|f| (f.clone(), mtime(&f))).collect()
+}
+
+impl Cached {
+    fn is_fresh(&self) -> bool {
+        self.files.iter().all(|(f, t)| mtime(f) == *t)
+    }
+}
+
+fn read(cache: &Path) -> Option<Cached> {
+    serde_json::from_str(&fs::read_to_string(cache).ok()?).ok()
+}
+
+/// The most caches to keep, see [`dirs::prune`]
+const MAX_CACHES: usize = 32;
+
+/// The compiled table next to `cache`
+fn compiled_path(cache: &Path) -> PathBuf {
+    cache.with_extension("compiled.json")
+}
+
+/// Write to a temporary file and rename it, so that concurrent runs never see
+/// a partial cache.
+fn write(cache: &Path, cached: &impl serde::Serialize) {
+    let tmp = cache.with_extension(format!("tmp.{}", std::process::id()));
+    let result = serde_json::to_string(cached)
+        .map_err(io::Error::other)
+        .and_then(|s| {
+            if let Some(dir) = cache.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&tmp, s)
+        })
+        .and_then(|()| fs::rename(&tmp, cache));
+    if let Err(e) = result {
+        debug!("Couldn't write {}: {e}", cache.display());
+    }
+}
+
+/// What happened when loading the config
+#[derive(Debug)]
+enum Outcome {
+    /// The config was reused, or reloaded if `fresh`
+    Loaded { conf: ConfigFile, fresh: bool },
+    /// The config didn't load, so the last good one was used. `new` is whether
+    /// the error differs from the one seen last time.
+    Stale {
+        conf: ConfigFile,
+        error: ConfigFileError,
+        new: bool,
+    },
+}
+
+/// Reuse the config at `cache` if none of its files changed, unless `force`.
+/// Otherwise, reload it, and record it and its compiled table if it loads.
+/// `candidates` are files that would be loaded if they existed.
+fn load_cached(
+    cache: &Path,
+    candidates: Vec<PathBuf>,
+    force: bool,
+    load: impl FnOnce() -> Result<ConfigFile, ConfigFileError>,
+) -> Result<Outcome, ConfigFileError> {
+    let compiled = compiled_path(cache);
+    let with_compiled = |mut conf: ConfigFile| {
+        conf.compiled = Some(compiled.clone());
+        conf
+    };
+    let cached = match read(cache) {
+        Some(c) if !force && c.is_fresh() => {
+            debug!("Reusing {}", cache.display());
+            return Ok(Outcome::Loaded {
+                conf: with_compiled(c.conf),
+                fresh: false,
+            });
+        }
+        cached => cached,
+    };
+    match (load(), cached) {
+        (Ok(mut conf), _) => {
+            let sources = std::mem::take(&mut conf.sources);
+            let files = stamp(candidates.into_iter().chain(sources));
+            write(&compiled, &conf.compile("", true));
+            let cached = Cached {
+                files,
+                conf,
+                error: None,
+            };
+            write(cache, &cached);
+            if let Some(dir) = cache.parent() {
+                dirs::prune(dir, MAX_CACHES);
+            }
+            Ok(Outcome::Loaded {
+                conf: with_compiled(cached.conf),
+                fresh: true,
+            })
+        }
+        (Err(e), None) => Err(e),
+        (Err(e), Some(mut cached)) => {
+            let message = e.to_string();
+            let new = cached.error.as_ref() != Some(&message);
+            if new {
+                cached.error = Some(message);
+                write(cache, &cached);
+            }
+            Ok(Outcome::Stale {
+                conf: with_compiled(cached.conf),
+                error: e,
+                new,
+            })
+        }
+    }
+}
+
+/// The cache for the config found by [`layers::locate`], layered with the
+/// `candidates` that exist
+fn cache_for(base: Option<&Path>, candidates: &[PathBuf]) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    base.hash(&mut hasher);
+    for layer in candidates.iter().filter(|c| c.is_file()) {
+        layer.hash(&mut hasher);
+    }
+    let name = format!("{:016x}.json", hasher.finish());
+    dirs::cache_dir().map(|d| d.join("config").join(name))
+}
+
+fn load_in_cwd(conf: Option<PathBuf>, force: bool) -> Result<Outcome, ConfigFileError> {
+    let base = layers::locate(conf);
+    let dir = std::env::current_dir().unwrap_or_default();
+    let load = || layers::load(base.clone());
+    // A new zbr may bundle a new default config
+    let candidates = layers::candidates(&dir)
+        .into_iter()
+        .chain(std::env::current_exe().ok())
+        .collect::<Vec<_>>();
+    match cache_for(base.as_deref(), &candidates) {
+        Some(cache) => load_cached(&cache, candidates, force, load),
+        None => load().map(|conf| Outcome::Loaded { conf, fresh: true }),
+    }
+}
+
+/// Like [`layers::load`], but reuses the config from the last run if none of
+/// its files changed, and falls back to it if the new config doesn't load.
+/// Conflicting abbreviations are left to `zbr check` and `zbr reload`, since
+/// the widget runs this after every keystroke.
+pub(super) fn load(conf: Option<PathBuf>) -> Result<ConfigFile, ConfigFileError> {
+    match load_in_cwd(conf, false)? {
+        Outcome::Loaded { conf, fresh } => {
+            if fresh {
+                debug!("Reloaded the config");
+            }
+            Ok(conf)
+        }
+        Outcome::Stale { conf, error, new } => {
+            if new {
+                warn!("Using the last good config: {error}");
+            }
+            Ok(conf)
+        }
+    }
+}
+
+/// Reload the config now, and report conflicting abbreviations. Returns
+/// whether it loaded.
+fn reload(conf: Option<PathBuf>) -> bool {
+    match load_in_cwd(conf, true) {
+        Ok(Outcome::Loaded { conf, .. }) => {
+            check::report_conflicts(&conf);
+            true
+        }
+        Ok(Outcome::Stale { error, .. }) => {
+            warn!("Keeping the last good config: {error}");
+            false
+        }
+        Err(e) => {
+            warn!("{e}");
+            false
+        }
+    }
+}
+
+/// The files that the config in the current directory depends on. The layers
+/// of other directories aren't watched.
+fn watched(conf: Option<PathBuf>) -> Vec<PathBuf> {
+    let base = layers::locate(conf);
+    let dir = std::env::current_dir().unwrap_or_default();
+    let candidates = layers::candidates(&dir);
+    cache_for(base.as_deref(), &candidates)
+        .and_then(|c| read(&c))
+        .map(|c| c.files.into_keys().collect())
+        .unwrap_or_else(|| candidates.into_iter().chain(base).collect())
+}
+
+/// Reload whenever one of the [`watched`] files changes. Directories are
+/// watched rather than files, because editors often replace files.
+#[cfg(target_os = "linux")]
+fn watch(conf: Option<PathBuf>) -> io::Result<()> {
+    use std::collections::BTreeSet;
+
+    use inotify::{Inotify, WatchMask};
+
+    let mask = WatchMask::CLOSE_WRITE
+        | WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::MODIFY
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO;
+    let mut buffer = [0; 4096];
+    loop {
+        let files = watched(conf.clone());
+        let mut inotify = Inotify::init()?;
+        let dirs = BTreeSet::from_iter(files.iter().filter_map(|f| f.parent()));
+        for dir in dirs.into_iter().filter(|d| d.is_dir()) {
+            inotify.watches().add(dir, mask)?;
+        }
+        let names = BTreeSet::from_iter(files.iter().filter_map(|f| f.file_name()));
+        loop {
+            let events = inotify.read_events_blocking(&mut buffer)?;
+            if events.filter_map(|e| e.name).any(|n| names.contains(n)) {
+                break;
+            }
+        }
+        if reload(conf.clone()) {
+            info!("Reloaded the config");
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch(_conf: Option<PathBuf>) -> io::Result<()> {
+    Err(io::Error::other("watching is only supported on Linux"))
+}
+
+/// Reload the config, and then keep reloading it if `keep_watching`. Returns
+/// whether the config loaded.
+pub(super) fn go(conf: Option<PathBuf>, keep_watching: bool) -> io::Result<bool> {
+    let loaded = reload(conf.clone());
+    if keep_watching {
+        watch(conf)?;
+    }
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{Outcome, compiled_path, load_cached};
+    use crate::zle::dirs::TempDir;
+    use crate::zle::expand::{ConfigFile, expand};
+
+    #[test]
+    fn test_load_cached() {
+        let dir = TempDir::new("reload");
+        let cache = dir.join("cache.json");
+        let path = dir.join("config.toml");
+        let missing = dir.join(".zbr.toml");
+        let load = |force: bool| {
+            let candidates = vec![missing.clone()];
+            load_cached(&cache, candidates, force, || ConfigFile::from_file(&path))
+        };
+        let gs = |conf: ConfigFile| expand(conf, String::from("gs"), String::new(), Some(false));
+
+        fs::write(
+            &path,
+            "[cmds.git]\nshort = \"g\"\n[cmds.git.subs.status]\nshort = \"s\"\n",
+        )
+        .unwrap();
+        let Ok(Outcome::Loaded { conf, fresh: true }) = load(false) else {
+            panic!("didn't load");
+        };
+        assert!(compiled_path(&cache).is_file());
+        assert_eq!(conf.compile_all()["gs"], "git status ");
+        assert_eq!(gs(conf).as_deref(), Some("git status "));
+        let Ok(Outcome::Loaded { fresh: false, .. }) = load(false) else {
+            panic!("didn't reuse");
+        };
+
+        // A broken config falls back to the last good one, and the error is
+        // only new the first time
+        fs::write(&path, "[cmds.git]\nshrot = \"g\"\n").unwrap();
+        let Ok(Outcome::Stale { conf, new, .. }) = load(false) else {
+            panic!("didn't fall back");
+        };
+        assert!(new);
+        assert_eq!(gs(conf).as_deref(), Some("git status "));
+        let Ok(Outcome::Stale { new, .. }) = load(true) else {
+            panic!("didn't fall back");
+        };
+        assert!(!new);
+
+        // Creating a layer that didn't exist invalidates the cache
+        fs::write(&path, "[cmds.git]\nshort = \"g\"\n").unwrap();
+        assert!(matches!(load(false), Ok(Outcome::Loaded { .. })));
+        fs::write(&missing, "").unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(load(false), Ok(Outcome::Stale { .. })));
+    }
+}
@@ -0,0 +1,48 @@
+use schemars::{Schema, schema_for};
+
+use super::{expand, extract};
+
+/// The config formats
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum Kind {
+    /// Configs for `zbr extract` and `zbr build`
+    Extract,
+    /// Configs for `zbr init` and the other commands
+    Runtime,
+}
+
+fn schema(kind: Kind) -> Schema {
+    match kind {
+        Kind::Extract => schema_for!(extract::ConfigFile),
+        Kind::Runtime => schema_for!(expand::ConfigFile),
+    }
+}
+
+pub(super) fn go(kind: Kind) {
+    let schema = schema(kind);
+    // Serializing a `serde_json::Value` can't fail
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema).unwrap_or_default()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Kind, schema};
+
+    fn properties(kind: Kind) -> Vec<String> {
+        let schema = schema(kind);
+        let props = schema.get("properties").and_then(|p| p.as_object());
+        props.into_iter().flat_map(|p| p.keys().cloned()).collect()
+    }
+
+    #[test]
+    fn test_schema() {
+        assert_eq!(
+            properties(Kind::Runtime),
+            ["cmds", "disable", "include", "override", "shadowed"]
+        );
+        assert!(properties(Kind::Extract).contains(&String::from("devowel")));
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{BTreeSet, HashSet};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use std::{env, fmt, thread};
+
+use tracing::{debug, warn};
+
+use super::dirs::{self, Cache};
+use super::path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Kind {
+    Alias,
+    Builtin,
+    Executable,
+    Function,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Alias => write!(f, "alias"),
+            Kind::Builtin => write!(f, "builtin"),
+            Kind::Executable => write!(f, "executable"),
+            Kind::Function => write!(f, "function"),
+        }
+    }
+}
+
+const BUILTINS: &str = "zbr-builtins";
+const ALIASES: &str = "zbr-aliases";
+const FUNCTIONS: &str = "zbr-functions";
+const EXECUTABLES: &str = "zbr-executables";
+
+/// How long to keep a snapshot of [`Names`] around
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How long to wait for zsh, see [`zsh`]
+const ZSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Run `script` in a zsh that doesn't read the user's zshrc, and return what it
+/// prints, unless it takes longer than [`ZSH_TIMEOUT`]
+fn zsh(script: &str) -> Option<String> {
+    let mut child = Command::new("zsh")
+        .arg("-fc")
+        .arg(script)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .inspect_err(|e| debug!("Couldn't run zsh: {e}"))
+        .ok()?;
+    // The output fits in the pipe's buffer, so zsh doesn't block on it
+    let deadline = Instant::now() + ZSH_TIMEOUT;
+    while child.try_wait().ok()?.is_none() {
+        if Instant::now() >= deadline {
+            drop(child.kill());
+            drop(child.wait());
+            warn!("Timed out asking zsh for its builtins");
+            return None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let output = child.wait_with_output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Names that already mean something to zsh
+#[derive(Debug, Default)]
+pub(super) struct Names {
+    aliases: HashSet<String>,
+    builtins: HashSet<String>,
+    executables: HashSet<String>,
+    functions: HashSet<String>,
+}
+
+impl Names {
+    /// Look at `$PATH`, and ask zsh for its builtins. zsh runs without the
+    /// user's zshrc, which may hang or prompt, so the `aliases` and
+    /// `functions` it defines are passed in by the shell that has them.
+    pub(super) fn detect(aliases: Vec<String>, functions: Vec<String>) -> Self {
+        let script = format!("print -rl -- {BUILTINS} ${{(k)builtins}} ${{(k)reswords}}");
+        let mut names = Self::parse(&zsh(&script).unwrap_or_default());
+        names.aliases.extend(aliases);
+        names.functions.extend(functions);
+        names.executables = path::executables();
+        names
+    }
+
+    /// Parse the output of the script in [`Names::detect`]. Anything that
+    /// `/etc/zshenv` prints before the first marker is ignored.
+    fn parse(out: &str) -> Self {
+        let mut names = Self::default();
+        let mut current = None;
+        for line in out.lines() {
+            match line {
+                BUILTINS => current = Some(&mut names.builtins),
+                ALIASES => current = Some(&mut names.aliases),
+                FUNCTIONS => current = Some(&mut names.functions),
+                EXECUTABLES => current = Some(&mut names.executables),
+                _ => {
+                    if let Some(set) = &mut current {
+                        set.insert(String::from(line));
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    fn serialize(&self) -> String {
+        let mut s = String::new();
+        for (marker, set) in [
+            (BUILTINS, &self.builtins),
+            (ALIASES, &self.aliases),
+            (FUNCTIONS, &self.functions),
+            (EXECUTABLES, &self.executables),
+        ] {
+            s.push_str(marker);
+            s.push('\n');
+            for name in BTreeSet::from_iter(set) {
+                s.push_str(name);
+                s.push('\n');
+            }
+        }
+        s
+    }
+
+    /// Like [`Names::detect`], but cached for a while. The cache is keyed on
+    /// `$PATH`.
+    pub(super) fn cached() -> Self {
+        let path_var = env::var("PATH").unwrap_or_default();
+        let Some(dir) = dirs::cache_dir() else {
+            return Self::detect(Vec::new(), Vec::new());
+        };
+        let cache = Cache::new(&dir, "names", (), &path_var);
+        if let Some(names) = cache.read(Some(CACHE_TTL)) {
+            return Self::parse(&names);
+        }
+        let names = Self::detect(Vec::new(), Vec::new());
+        cache.write(&names.serialize());
+        names
+    }
+
+    /// What zsh would run for `name`, in order of precedence
+    pub(super) fn shadows(&self, name: &str) -> Option<Kind> {
+        if self.aliases.contains(name) {
+            Some(Kind::Alias)
+        } else if self.functions.contains(name) {
+            Some(Kind::Function)
+        } else if self.builtins.contains(name) {
+            Some(Kind::Builtin)
+        } else if self.executables.contains(name) {
+            Some(Kind::Executable)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Kind, Names};
+
+    #[test]
+    fn test_parse() {
+        let names = Names::parse(
+            "welcome!\nzbr-builtins\ncd\nif\nzbr-aliases\nll\nzbr-functions\nmkcd\ncd\n",
+        );
+        assert_eq!(names.shadows("cd"), Some(Kind::Function));
+        assert_eq!(names.shadows("if"), Some(Kind::Builtin));
+        assert_eq!(names.shadows("ll"), Some(Kind::Alias));
+        assert_eq!(names.shadows("mkcd"), Some(Kind::Function));
+        assert_eq!(names.shadows("welcome!"), None);
+    }
+
+    #[test]
+    fn test_serialize() {
+        let names = Names::parse("zbr-builtins\ncd\nzbr-executables\ntig\n");
+        let names = Names::parse(&names.serialize());
+        assert_eq!(names.shadows("cd"), Some(Kind::Builtin));
+        assert_eq!(names.shadows("tig"), Some(Kind::Executable));
+    }
+
+    #[test]
+    fn test_detect() {
+        let names = Names::detect(vec![String::from("gst")], vec![String::from("mkcd")]);
+        assert_eq!(names.shadows("gst"), Some(Kind::Alias));
+        assert_eq!(names.shadows("mkcd"), Some(Kind::Function));
+    }
+}
@@ -0,0 +1,345 @@
+//! Expansions are recorded in an append-only log, one per line, with tab-
+//! separated fields: the time, the abbreviation, the expansion, and the working
+//! directory. Once the log grows past [`MAX_LOG`] bytes, it's rotated, and the
+//! previous log is kept next to it.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::debug;
+
+use super::dirs;
+use super::expand::clean_buf;
+use super::extract::Cmds;
+use super::rank::Popularity;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// The size at which the log is rotated
+const MAX_LOG: u64 = 1 << 20;
+
+fn log() -> Option<PathBuf> {
+    dirs::state_dir().map(|d| d.join("expansions.log"))
+}
+
+/// The log before the last rotation
+fn previous(log: &Path) -> PathBuf {
+    log.with_extension("log.1")
+}
+
+/// The previous log and the current one
+fn logs() -> Vec<PathBuf> {
+    log().map(|l| vec![previous(&l), l]).unwrap_or_default()
+}
+
+/// Replace the previous log with the current one, if it's past [`MAX_LOG`]
+fn rotate(log: &Path) {
+    if fs::metadata(log).is_ok_and(|m| m.len() > MAX_LOG)
+        && let Err(e) = fs::rename(log, previous(log))
+    {
+        debug!("Couldn't rotate {}: {e}", log.display());
+    }
+}
+
+/// An expansion of an abbreviation
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    /// Seconds since the epoch
+    time: u64,
+    short: String,
+    long: String,
+    cwd: String,
+}
+
+impl Entry {
+    fn to_line(&self) -> String {
+        let clean = |s: &str| s.replace(['\t', '\n'], " ");
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            self.time,
+            clean(&self.short),
+            clean(&self.long),
+            clean(&self.cwd)
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        Some(Self {
+            time: fields.next()?.parse().ok()?,
+            short: String::from(fields.next()?),
+            long: String::from(fields.next()?),
+            cwd: String::from(fields.next()?),
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Record that `lbuf` was expanded to `expanded`, see [`super::expand::expand`]
+pub(super) fn record(lbuf: String, expanded: &str) {
+    let Some(log) = log() else {
+        return;
+    };
+    let (prefix, short) = clean_buf(lbuf);
+    let entry = Entry {
+        time: now(),
+        short: String::from(short.trim_end()),
+        long: String::from(
+            expanded
+                .strip_prefix(&prefix)
+                .unwrap_or(expanded)
+                .trim_end(),
+        ),
+        cwd: std::env::current_dir()
+            .map(|d| d.display().to_string())
+            .unwrap_or_default(),
+    };
+    rotate(&log);
+    // Appends this small are atomic, so concurrent shells don't interleave
+    let result = log
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| OpenOptions::new().create(true).append(true).open(&log))
+        .and_then(|mut f| f.write_all(entry.to_line().as_bytes()));
+    if let Err(e) = result {
+        debug!("Couldn't write {}: {e}", log.display());
+    }
+}
+
+fn read() -> Vec<Entry> {
+    logs()
+        .iter()
+        .filter_map(|l| fs::read_to_string(l).ok())
+        .flat_map(|s| s.lines().filter_map(Entry::from_line).collect::<Vec<_>>())
+        .collect()
+}
+
+/// How often each expansion was recorded. The counts are cached, see
+/// [`Popularity::from_file`].
+pub(super) fn popularity() -> Popularity {
+    let cache = dirs::cache_dir();
+    let count = |s: &str| {
+        let longs = s.lines().filter_map(Entry::from_line).map(|e| e.long);
+        Popularity::from_commands(&longs.collect::<Vec<_>>().join("\n"))
+    };
+    let mut popularity = Popularity::default();
+    for log in logs() {
+        popularity.add(Popularity::from_file(
+            &log,
+            cache.as_deref(),
+            "usage",
+            count,
+        ));
+    }
+    popularity
+}
+
+fn ago(now: u64, time: u64) -> String {
+    match now.saturating_sub(time) / DAY {
+        0 => String::from("today"),
+        1 => String::from("yesterday"),
+        n => format!("{n} days ago"),
+    }
+}
+
+/// The commands in `cmds`, which are subcommands of `parent`, that no
+/// expansion in `expanded` starts with, and the flags of the other commands
+/// that none of their expansions have. Each is listed under its parent.
+fn unused<'a>(
+    cmds: &'a Cmds,
+    parent: &[&'a str],
+    expanded: &[Vec<&str>],
+    out: &mut Vec<(String, Vec<&'a str>)>,
+) {
+    let mut names = Vec::new();
+    let mut used = Vec::new();
+    for (long, cmd) in cmds.enabled() {
+        let mut path = parent.to_vec();
+        path.push(long);
+        let uses = expanded
+            .iter()
+            .filter(|e| e.starts_with(&path))
+            .collect::<Vec<_>>();
+        if uses.is_empty() {
+            names.push(long.as_str());
+        } else {
+            used.push((path, cmd, uses));
+        }
+    }
+    for (path, cmd, uses) in used {
+        let mut flags = cmd
+            .flags
+            .iter()
+            .filter(|(flag, f)| {
+                !uses.iter().any(|e| {
+                    e[path.len()..]
+                        .iter()
+                        .any(|w| w == flag || Some(*w) == f.alias.as_deref())
+                })
+            })
+            .map(|(flag, _)| flag.as_str())
+            .collect::<Vec<_>>();
+        flags.sort_unstable();
+        let mut sub_out = Vec::new();
+        unused(&cmd.subs, &path, expanded, &mut sub_out);
+        let name = path.join(" ");
+        match sub_out.first_mut() {
+            Some((n, subs)) if *n == name => subs.extend(flags),
+            _ if !flags.is_empty() => sub_out.insert(0, (name, flags)),
+            _ => (),
+        }
+        out.extend(sub_out);
+    }
+    if !names.is_empty() {
+        out.insert(0, (parent.join(" "), names));
+    }
+}
+
+/// How often each abbreviation was used and when it was last used, and the
+/// commands, subcommands, and flags in `cmds` that were never expanded
+fn summarize(entries: &[Entry], cmds: &Cmds, now: u64) -> String {
+    let mut uses = BTreeMap::<(&str, &str), (usize, u64)>::new();
+    for e in entries {
+        let (n, last) = uses.entry((&e.short, &e.long)).or_default();
+        *n += 1;
+        *last = (*last).max(e.time);
+    }
+    let mut uses = Vec::from_iter(uses);
+    uses.sort_by(|(a, (n, _)), (b, (m, _))| m.cmp(n).then(a.cmp(b)));
+
+    let mut s = format!(
+        "{} expansions of {} abbreviations\n",
+        entries.len(),
+        uses.len()
+    );
+    if !uses.is_empty() {
+        let _ = writeln!(s, "\n{:>6}  {:<12}  abbreviation", "uses", "last used");
+    }
+    for ((short, long), (n, last)) in &uses {
+        let _ = writeln!(s, "{n:>6}  {:<12}  {short} --> {long}", ago(now, *last));
+    }
+    let expanded = entries
+        .iter()
+        .map(|e| e.long.split_whitespace().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut never = Vec::new();
+    unused(cmds, &[], &expanded, &mut never);
+    if !never.is_empty() {
+        s.push('\n');
+    }
+    for (parent, names) in never {
+        let within = if parent.is_empty() {
+            String::new()
+        } else {
+            format!(" in {parent}")
+        };
+        let _ = writeln!(s, "Never expanded{within}: {}", names.join(", "));
+    }
+    s
+}
+
+pub(super) fn report(cmds: &Cmds) {
+    print!("{}", summarize(&read(), cmds, now()));
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use std::fs;
+
+    use super::{DAY, Entry, MAX_LOG, previous, rotate, summarize};
+    use crate::zle::dirs::TempDir;
+    use crate::zle::expand::ConfigFile;
+
+    #[test]
+    fn test_entry() {
+        let entry = Entry {
+            time: 1,
+            short: String::from("gs"),
+            long: String::from("git status"),
+            cwd: String::from("/tmp/a\tb"),
+        };
+        let parsed = Entry::from_line(entry.to_line().trim_end()).unwrap();
+        assert_eq!(parsed.long, "git status");
+        assert_eq!(parsed.cwd, "/tmp/a b");
+    }
+
+    #[test]
+    fn test_rotate() {
+        let dir = TempDir::new("rotate");
+        let log = dir.join("expansions.log");
+        fs::write(&log, "").unwrap();
+        rotate(&log);
+        assert!(log.is_file());
+        let size = usize::try_from(MAX_LOG).unwrap() + 1;
+        fs::write(&log, "\n".repeat(size)).unwrap();
+        rotate(&log);
+        assert!(!log.exists());
+        assert!(previous(&log).is_file());
+    }
+
+    #[test]
+    fn test_summarize() {
+        let conf = toml::from_str::<ConfigFile>(
+            r#"
+            [cmds.git]
+            short = "g"
+            [cmds.git.flags.--verbose]
+            short = "v"
+            [cmds.git.subs.status]
+            short = "s"
+            [cmds.git.subs.stash]
+            short = "sh"
+            [cmds.git.subs.commit]
+            short = "c"
+            [cmds.git.subs.commit.flags.--amend]
+            short = "a"
+            [cmds.git.subs.commit.flags.--message]
+            short = "m"
+            alias = "-m"
+            [cmds.cargo]
+            short = "c"
+            [cmds.just]
+            short = "j"
+            "#,
+        )
+        .unwrap();
+        let entry = |time, short: &str, long: &str| Entry {
+            time,
+            short: String::from(short),
+            long: String::from(long),
+            cwd: String::from("/"),
+        };
+        let now = 10 * DAY;
+        let entries = [
+            entry(now - 3 * DAY, "gs", "git status"),
+            entry(now, "gs", "git status"),
+            entry(now - DAY, "j", "just"),
+            entry(now - DAY, "gcm", "git commit -m"),
+        ];
+        let expected = expect![[r#"
+            4 expansions of 3 abbreviations
+
+              uses  last used     abbreviation
+                 2  today         gs --> git status
+                 1  yesterday     gcm --> git commit -m
+                 1  yesterday     j --> just
+
+            Never expanded: cargo
+            Never expanded in git: stash, --verbose
+            Never expanded in git commit: --amend
+        "#]];
+        expected.assert_eq(&summarize(&entries, &conf.cmds, now));
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use toml::Spanned;
+use toml::de::{DeTable, DeValue};
+
+use super::expand::{ConfigFile, resolve_include};
+use super::layers;
+
+/// A problem at a location in a config
+#[derive(Debug, PartialEq, Eq)]
+struct Diagnostic {
+    span: Range<usize>,
+    message: String,
+}
+
+impl Diagnostic {
+    fn new(span: Range<usize>, message: String) -> Self {
+        Self { span, message }
+    }
+
+    /// e.g.,
+    ///
+    /// ```text
+    /// conf.toml:3:9: empty short for `git status`
+    ///   |
+    /// 3 | short = ""
+    ///   |         ^^
+    /// ```
+    fn render(&self, path: &str, src: &str) -> String {
+        let start = self.span.start.min(src.len());
+        let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+        let line = src[..start].matches('\n').count() + 1;
+        let col = src[line_start..start].chars().count() + 1;
+        let text = &src[line_start..line_end];
+        let width = src[start..self.span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = " ".repeat(line.to_string().len());
+        let mut s = format!("{path}:{line}:{col}: {}\n", self.message);
+        let _ = writeln!(s, "{gutter} |");
+        let _ = writeln!(s, "{line} | {text}");
+        let _ = writeln!(s, "{gutter} | {}{}", " ".repeat(col - 1), "^".repeat(width));
+        s
+    }
+}
+
+/// A subcommand or flag, and its short
+struct Child<'a> {
+    name: String,
+    short: &'a Spanned<DeValue<'a>>,
+}
+
+/// Shorts must be non-empty words that are unique among their siblings.
+fn check_shorts(children: &mut [Child<'_>], diags: &mut Vec<Diagnostic>) {
+    children.sort_by_key(|c| c.short.span().start);
+    let mut seen = Vec::<(&str, &str)>::with_capacity(children.len());
+    for child in children.iter() {
+        let Some(short) = child.short.get_ref().as_str() else {
+            continue;
+        };
+        let span = child.short.span();
+        let name = &child.name;
+        if short.is_empty() {
+            diags.push(Diagnostic::new(span, format!("empty short for `{name}`")));
+            continue;
+        }
+        if short.contains(char::is_whitespace) {
+            diags.push(Diagnostic::new(
+                span.clone(),
+                format!("short `{short}` for `{name}` contains whitespace"),
+            ));
+        }
+        if let Some((_, other)) = seen.iter().find(|(s, _)| *s == short) {
+            diags.push(Diagnostic::new(
+                span,
+                format!("short `{short}` for `{name}` is also the short for `{other}`"),
+            ));
+        }
+        seen.push((short, name));
+    }
+}
+
+/// Squished flags are typed without a `-`, so their shorts must also differ
+/// from the shorts of the subcommands of the same command.
+fn check_squished(subs: &[Child<'_>], squished: &[Child<'_>], diags: &mut Vec<Diagnostic>) {
+    for flag in squished {
+        let Some(short) = flag.short.get_ref().as_str() else {
+            continue;
+        };
+        if let Some(sub) = subs
+            .iter()
+            .find(|s| s.short.get_ref().as_str() == Some(short))
+        {
+            diags.push(Diagnostic::new(
+                flag.short.span(),
+                format!(
+                    "short `{short}` for squished flag `{}` is also the short for `{}`",
+                    flag.name, sub.name
+                ),
+            ));
+        }
+    }
+}
+
+fn table<'a>(value: &'a Spanned<DeValue<'a>>) -> Option<&'a DeTable<'a>> {
+    value.get_ref().as_table()
+}
+
+/// Check `cmds`, the subcommands of `prefix`, and their descendants. Flags are
+/// typed with a `-`, so their shorts only need to be unique among each other,
+/// except for the `squished` flags of `prefix`, see [`check_squished`].
+fn check_cmds<'a>(
+    prefix: &str,
+    cmds: Option<&'a DeTable<'a>>,
+    squished: &[Child<'a>],
+    diags: &mut Vec<Diagnostic>,
+) {
+    let mut children = Vec::new();
+    for (long, cmd) in cmds.into_iter().flatten() {
+        let name = format!("{prefix}{}", long.get_ref());
+        let Some(cmd) = table(cmd) else {
+            continue;
+        };
+        if let Some(short) = cmd.get("short") {
+            children.push(Child {
+                name: name.clone(),
+                short,
+            });
+        }
+
+        let mut flag_shorts = Vec::new();
+        let mut squished_shorts = Vec::new();
+        if let Some(flags) = cmd.get("flags").and_then(table) {
+            for (flag, value) in flags {
+                if !flag.get_ref().starts_with('-') {
+                    diags.push(Diagnostic::new(
+                        flag.span(),
+                        format!(
+                            "flag `{}` of `{name}` doesn't start with `-`",
+                            flag.get_ref()
+                        ),
+                    ));
+                }
+                let Some(value) = table(value) else {
+                    continue;
+                };
+                if let Some(short) = value.get("short") {
+                    let child = || Child {
+                        name: format!("{name} {}", flag.get_ref()),
+                        short,
+                    };
+                    if value
+                        .get("squish")
+                        .is_some_and(|s| s.get_ref().as_bool() == Some(true))
+                    {
+                        squished_shorts.push(child());
+                    }
+                    flag_shorts.push(child());
+                }
+            }
+        }
+        let subs = cmd.get("subs").and_then(table);
+        if let Some(no_args) = cmd.get("no_args")
+            && no_args.get_ref().as_bool() == Some(true)
+            && subs.is_some_and(|s| !s.is_empty())
+        {
+            diags.push(Diagnostic::new(
+                no_args.span(),
+                format!("`{name}` has `no_args`, but also has subcommands"),
+            ));
+        }
+        check_shorts(&mut flag_shorts, diags);
+        check_cmds(&format!("{name} "), subs, &squished_shorts, diags);
+    }
+    check_squished(&children, squished, diags);
+    check_shorts(&mut children, diags);
+}
+
+/// Syntax errors, unknown fields, and the problems described in
+/// [`check_cmds`]
+fn validate(src: &str) -> Vec<Diagnostic> {
+    if let Err(e) = toml::from_str::<ConfigFile>(src) {
+        let span = e.span().unwrap_or(0..0);
+        return vec![Diagnostic::new(span, String::from(e.message().trim_end()))];
+    }
+    let mut diags = Vec::new();
+    if let Ok(doc) = DeTable::parse(src) {
+        let cmds = doc.get_ref().get("cmds").and_then(table);
+        check_cmds("", cmds, &[], &mut diags);
+    }
+    diags.sort_by_key(|d| d.span.start);
+    diags
+}
+
+/// Validate `path` and the configs it includes. `stack` holds the configs
+/// that include it.
+fn validate_file(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    done: &mut HashSet<PathBuf>,
+    out: &mut Vec<String>,
+) {
+    let src = match fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(e) => {
+            out.push(format!("{}: couldn't read config: {e}\n", path.display()));
+            return;
+        }
+    };
+    let name = path.display().to_string();
+    out.extend(validate(&src).iter().map(|d| d.render(&name, &src)));
+    let Ok(conf) = toml::from_str::<ConfigFile>(&src) else {
+        return;
+    };
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        out.push(format!("{name}: config includes itself\n"));
+        return;
+    }
+    if !done.insert(canonical.clone()) {
+        return;
+    }
+    stack.push(canonical);
+    for include in &conf.include {
+        validate_file(&resolve_include(path, include), stack, done, out);
+    }
+    stack.pop();
+}
+
+/// Validate the config found by [`layers::locate`], or else the bundled
+/// default. Returns whether there were any problems.
+pub(super) fn go(conf: Option<PathBuf>) -> bool {
+    let mut out = Vec::new();
+    match layers::locate(conf) {
+        Some(path) => validate_file(&path, &mut Vec::new(), &mut HashSet::new(), &mut out),
+        None => {
+            let src = layers::BUNDLED;
+            let diags = validate(src);
+            out.extend(diags.iter().map(|d| d.render(layers::BUNDLED_PATH, src)));
+        }
+    }
+    for o in &out {
+        print!("{o}");
+    }
+    !out.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+
+    use super::validate;
+
+    fn render(src: &str) -> String {
+        validate(src)
+            .iter()
+            .map(|d| d.render("conf.toml", src))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_ok() {
+        let src = r#"
+include = ["~/.config/zbr/work.toml"]
+[cmds.git]
+short = "g"
+[cmds.git.flags.--amend]
+short = "a"
+[cmds.git.subs.add]
+short = "a"
+[cmds.git.subs.status]
+short = "s"
+no_args = true
+"#;
+        assert_eq!(render(src), "");
+    }
+
+    #[test]
+    fn test_validate_syntax() {
+        let expected = expect![[r#"
+            conf.toml:3:1: unknown field `shrot`, expected one of `short`, `desc`, `flags`, `no_args`, `subs`, `values`, `disable`
+              |
+            3 | shrot = "g"
+              | ^^^^^
+        "#]];
+        expected.assert_eq(&render("[cmds.git]\nshort = \"g\"\nshrot = \"g\"\n"));
+    }
+
+    #[test]
+    fn test_validate_semantics() {
+        let src = r#"
+[cmds.git]
+short = "g"
+[cmds.git.flags.amend]
+short = "a"
+[cmds.git.subs.status]
+short = ""
+[cmds.git.subs.stash]
+short = "s h"
+[cmds.git.subs.add]
+short = "a"
+[cmds.git.flags.--all]
+short = "a"
+[cmds.git.flags.--amend]
+short = "a"
+[cmds.gh]
+short = "g"
+no_args = true
+[cmds.gh.subs.pr]
+short = "p"
+[cmds.git.subs.diff]
+short = "d"
+[cmds.git.subs.diff.flags.--cached]
+short = "s"
+squish = true
+[cmds.git.subs.diff.subs.stat]
+short = "s"
+"#;
+        let expected = expect![[r#"
+            conf.toml:4:17: flag `amend` of `git` doesn't start with `-`
+              |
+            4 | [cmds.git.flags.amend]
+              |                 ^^^^^
+            conf.toml:7:9: empty short for `git status`
+              |
+            7 | short = ""
+              |         ^^
+            conf.toml:9:9: short `s h` for `git stash` contains whitespace
+              |
+            9 | short = "s h"
+              |         ^^^^^
+            conf.toml:13:9: short `a` for `git --all` is also the short for `git amend`
+               |
+            13 | short = "a"
+               |         ^^^
+            conf.toml:15:9: short `a` for `git --amend` is also the short for `git amend`
+               |
+            15 | short = "a"
+               |         ^^^
+            conf.toml:17:9: short `g` for `gh` is also the short for `git`
+               |
+            17 | short = "g"
+               |         ^^^
+            conf.toml:18:11: `gh` has `no_args`, but also has subcommands
+               |
+            18 | no_args = true
+               |           ^^^^
+            conf.toml:24:9: short `s` for squished flag `git diff --cached` is also the short for `git diff stat`
+               |
+            24 | short = "s"
+               |         ^^^
+        "#]];
+        expected.assert_eq(&render(src));
+    }
+}
@@ -0,0 +1,119 @@
+use std::env;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use tracing::debug;
+
+use super::dirs::{self, Cache};
+
+fn default_ttl() -> u64 {
+    60
+}
+
+/// The values that a positional argument or a flag can take, e.g., `stable`
+/// and `nightly` for `rustup toolchain install`
+#[derive(
+    Clone,
+    Debug,
+    Eq,
+    Ord,
+    PartialEq,
+    PartialOrd,
+    schemars::JsonSchema,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+#[serde(untagged)]
+pub(super) enum Values {
+    /// A fixed list
+    List(Vec<String>),
+    /// The lines printed by a shell command, cached for `ttl` seconds
+    Command {
+        command: String,
+        #[serde(default = "default_ttl")]
+        ttl: u64,
+    },
+}
+
+impl Values {
+    /// Non-empty values without whitespace
+    pub(super) fn get(&self) -> Vec<String> {
+        let values = match self {
+            Values::List(vs) => vs.clone(),
+            Values::Command { command, ttl } => cached(command, Duration::from_secs(*ttl)),
+        };
+        values
+            .into_iter()
+            .filter(|v| !v.is_empty() && !v.contains(char::is_whitespace))
+            .collect()
+    }
+}
+
+fn run(command: &str) -> Vec<String> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .map(|l| String::from(l.trim()))
+            .collect(),
+        Ok(o) => {
+            debug!("`{command}` failed: {}", o.status);
+            Vec::new()
+        }
+        Err(e) => {
+            debug!("Couldn't run `{command}`: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// Like [`run`], but cached per command and working directory. Commands such
+/// as `git branch` depend on the latter.
+fn cached(command: &str, ttl: Duration) -> Vec<String> {
+    let cwd = env::current_dir().unwrap_or_default();
+    let key = format!("{}\t{command}", cwd.display());
+    let Some(dir) = dirs::cache_dir() else {
+        return run(command);
+    };
+    let cache = Cache::new(&dir, "values", &key, &key);
+    if let Some(values) = cache.read(Some(ttl)) {
+        return values.lines().map(String::from).collect();
+    }
+    let values = run(command);
+    cache.write(&values.join("\n"));
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Values, run};
+
+    #[test]
+    fn test_values() {
+        let values = |toml: &str| {
+            #[derive(serde::Deserialize)]
+            struct T {
+                values: Values,
+            }
+            toml::from_str::<T>(toml).unwrap().values
+        };
+        assert_eq!(
+            values(r#"values = ["stable", "nightly", "", "a b"]"#).get(),
+            ["stable", "nightly"]
+        );
+        assert_eq!(
+            values(r#"values = { command = "echo main", ttl = 0 }"#),
+            Values::Command {
+                command: String::from("echo main"),
+                ttl: 0
+            }
+        );
+        assert_eq!(run("printf 'main\\n  master\\n'"), ["main", "master"]);
+        assert!(run("exit 1").is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+use std::path::PathBuf;
+use std::process::exit;
+
+mod abbrev;
+mod aliases;
+mod check;
+mod compile;
+mod diff;
+mod dirs;
+mod expand;
+mod extract;
+mod hint;
+mod layers;
+mod path;
+mod project;
+mod rank;
+mod reload;
+mod schema;
+mod shadow;
+mod usage;
+mod validate;
+mod values;
+
+use self::expand::ConfigFileError;
+use self::extract::ExtractError;
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    Aliases {
+        #[arg(long)]
+        conf: Option<PathBuf>,
+    },
+    /// Extract and merge the tools listed in a manifest
+    Build(extract::Build),
+    /// Find abbreviations that are bound twice or shadow other commands
+    Check {
+        #[arg(long)]
+        conf: Option<PathBuf>,
+    },
+    /// Show abbreviations that differ between two configs
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+    },
+    Expand {
+        /// Whether the abbreviation is also an alias, builtin, function, or
+        /// executable
+        #[arg(long)]
+        shadowed: Option<bool>,
+        /// Record the expansion, see `zbr report`
+        #[arg(long)]
+        record: bool,
+
+        #[arg(long)]
+        conf: Option<PathBuf>,
+        lbuf: String,
+        rbuf: String,
+    },
+    Extract(extract::Config),
+    Hint {
+        #[arg(long, default_value_t = u8::MAX)]
+        max: u8,
+
+        #[arg(long)]
+        conf: Option<PathBuf>,
+        /// Also show abbreviations whose expansions contain the buffer as a
+        /// subsequence, e.g., `git submodule` for `gsm`
+        #[arg(long)]
+        fuzzy: bool,
+        /// Zsh history, to rank commonly-used commands first
+        #[arg(long)]
+        history: Option<PathBuf>,
+        #[arg(long, value_enum, default_value_t)]
+        format: hint::Format,
+        /// Show as many hints on each line as fit in this many columns
+        #[arg(long)]
+        width: Option<usize>,
+        buf: String,
+    },
+    /// Summarize the expansions recorded by `zbr expand --record`
+    Report {
+        #[arg(long)]
+        conf: Option<PathBuf>,
+    },
+    /// Reload the config now, rather than when it's next used
+    Reload {
+        #[arg(long)]
+        conf: Option<PathBuf>,
+        /// Keep reloading the config whenever it changes
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Print a JSON Schema for a config format, e.g., for editors
+    Schema {
+        #[arg(value_enum)]
+        kind: schema::Kind,
+    },
+    /// Let the `.zbr.toml` and `.zbr.local.toml` in a directory run commands
+    /// for `values` and include other configs
+    Trust {
+        /// Defaults to the current directory
+        dir: Option<PathBuf>,
+    },
+    /// Find syntax errors, unknown fields, and invalid shorts in a config
+    Validate {
+        #[arg(long)]
+        conf: Option<PathBuf>,
+    },
+    /// Print the zsh code that sets up zbr. Without a config, zbr looks for
+    /// `$ZBR_CONF`, `$XDG_CONFIG_HOME/zbr/config.toml`, or
+    /// `~/.config/zbr/config.toml` each time it runs, and otherwise uses a
+    /// bundled default.
+    Init {
+        #[arg(long)]
+        conf: Option<PathBuf>,
+        /// Show fuzzy hints, see `zbr hint --fuzzy`
+        #[arg(long)]
+        fuzzy: bool,
+        /// Record expansions, see `zbr report`
+        #[arg(long)]
+        record: bool,
+        /// Show hints in color below the command line, rather than with
+        /// `zle -M`
+        #[arg(long)]
+        color: bool,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Config(#[from] ConfigFileError),
+    #[error(transparent)]
+    Extract(#[from] ExtractError),
+    #[error("couldn't watch the config: {0}")]
+    Watch(std::io::Error),
+    #[error("couldn't trust {1}: {0}")]
+    Trust(std::io::Error, PathBuf),
+}
+
+pub fn go(cmd: Command) -> Result<(), Error> {
+    match cmd {
+        Command::Aliases { conf } => {
+            let conf = reload::load(conf)?;
+            aliases::go(conf);
+        }
+        Command::Build(conf) => extract::build(conf)?,
+        Command::Check { conf } => {
+            // Not the cached config, which reports conflicts when it reloads
+            let conf = layers::load(conf)?;
+            if check::go(conf) {
+                exit(1);
+            }
+        }
+        Command::Diff { old, new } => {
+            let old = expand::ConfigFile::from_file(old)?;
+            let new = expand::ConfigFile::from_file(new)?;
+            diff::go(old, new);
+        }
+        Command::Expand {
+            conf,
+            lbuf,
+            rbuf,
+            shadowed,
+            record,
+        } => {
+            let mut conf = reload::load(conf)?;
+            conf.add_project();
+            if let Some(result) = expand::expand(conf, lbuf.clone(), rbuf, shadowed) {
+                if record {
+                    usage::record(lbuf, &result);
+                }
+                println!("{result}");
+                exit(0);
+            }
+            exit(1)
+        }
+        Command::Extract(conf) => extract::go(conf)?,
+        Command::Hint {
+            conf,
+            buf,
+            max,
+            fuzzy,
+            history,
+            format,
+            width,
+        } => {
+            let mut conf = reload::load(conf)?;
+            conf.add_project();
+            let mut popularity = usage::popularity();
+            if let Some(h) = history {
+                let cache = dirs::cache_dir();
+                popularity.add(rank::Popularity::from_history(&h, cache.as_deref()));
+            }
+            let ranking = rank::Ranking { popularity, fuzzy };
+            let hints = hint::hint(&conf, buf.clone(), max as usize, &ranking);
+            print!("{}", hint::render(&conf, buf, &hints, format, width));
+        }
+        Command::Report { conf } => {
+            let conf = reload::load(conf)?;
+            usage::report(&conf.cmds);
+        }
+        Command::Reload { conf, watch } => {
+            if !reload::go(conf, watch).map_err(Error::Watch)? {
+                exit(1);
+            }
+        }
+        Command::Schema { kind } => schema::go(kind),
+        Command::Trust { dir } => {
+            let dir = dir.unwrap_or_else(|| PathBuf::from("."));
+            layers::trust(&dir).map_err(|e| Error::Trust(e, dir))?;
+        }
+        Command::Validate { conf } => {
+            if validate::go(conf) {
+                exit(1);
+            }
+        }
+        Command::Init {
+            conf,
+            fuzzy,
+            record,
+            color,
+        } => {
+            // e.g., `--conf 'it'\''s.toml'`
+            let args = conf.map_or_else(String::new, |c| {
+                format!("--conf '{}'", c.to_string_lossy().replace('\'', r"'\''"))
+            });
+            let hint_args = if fuzzy { "--fuzzy" } else { "" };
+            let expand_args = if record { "--record" } else { "" };
+            let display = if color { "postdisplay" } else { "message" };
+            let init = include_str!("init.zsh")
+                .replace("${ZBR_ARGS}", &args)
+                .replace("${ZBR_HINT_ARGS}", hint_args)
+                .replace("${ZBR_EXPAND_ARGS}", expand_args)
+                .replace("${ZBR_DISPLAY}", display);
+            println!("{init}");
+        }
+    }
+    Ok(())
+}